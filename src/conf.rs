@@ -0,0 +1,187 @@
+use std::ffi::{CStr, CString};
+use std::ptr;
+use libc::{c_char, int32_t};
+
+use native::*;
+use perm::FsPermission;
+
+/// Hadoop configuration which libhdfs loads from the XML configuration files
+/// (e.g., ```core-site.xml``` and ```hdfs-site.xml```) found in ```CLASSPATH```.
+///
+/// Keys of ```hdfs-default.xml``` and ```hdfs-site.xml``` may be visible only
+/// after connecting to HDFS, because Hadoop registers them on loading HDFS classes.
+pub struct HdfsConf;
+
+impl HdfsConf {
+
+  /// Get a configuration string. It returns None if the key is not found.
+  pub fn get_str(key: &str) -> Option<String> {
+    let c_key = match CString::new(key) {
+      Ok(c_key) => c_key,
+      Err(_) => return None
+    };
+    let mut val: *mut c_char = ptr::null_mut();
+
+    if unsafe { hdfsConfGetStr(c_key.as_ptr(), &mut val) } != 0 || val.is_null() {
+      return None;
+    }
+
+    let bytes = unsafe { CStr::from_ptr(val) }.to_bytes();
+    let res = String::from_utf8_lossy(bytes).into_owned();
+    unsafe { hdfsConfStrFree(val) };
+
+    Some(res)
+  }
+
+  /// Get a configuration integer. It returns None if the key is not found
+  /// or its value is not an integer.
+  pub fn get_int(key: &str) -> Option<i32> {
+    // hdfsConfGetInt leaves the value unchanged for a missing key,
+    // so a missing key could not be told from a zero value.
+    if HdfsConf::get_str(key).is_none() {
+      return None;
+    }
+
+    let c_key = match CString::new(key) {
+      Ok(c_key) => c_key,
+      Err(_) => return None
+    };
+    let mut val: int32_t = 0;
+
+    if unsafe { hdfsConfGetInt(c_key.as_ptr(), &mut val) } == 0 {
+      Some(val as i32)
+    } else {
+      None
+    }
+  }
+
+  /// Get a size in bytes, which may have a binary prefix suffix
+  /// (e.g., ```128m```) like ```Configuration.getLongBytes```.
+  pub fn get_bytes(key: &str) -> Option<u64> {
+    HdfsConf::get_str(key).and_then(|val| parse_bytes(&val))
+  }
+
+  /// The default filesystem URI (```fs.defaultFS```)
+  pub fn default_fs() -> Option<String> {
+    HdfsConf::get_str("fs.defaultFS")
+  }
+
+  /// The default replication factor (```dfs.replication```)
+  pub fn replication() -> Option<i16> {
+    HdfsConf::get_int("dfs.replication").map(|val| val as i16)
+  }
+
+  /// The default block size in bytes (```dfs.blocksize```)
+  pub fn block_size() -> Option<u64> {
+    HdfsConf::get_bytes("dfs.blocksize")
+  }
+
+  /// The buffer size used for reading and writing files (```io.file.buffer.size```)
+  pub fn buffer_size() -> Option<i32> {
+    HdfsConf::get_int("io.file.buffer.size")
+  }
+
+  /// The umask applied to new files and directories (```fs.permissions.umask-mode```).
+  /// It is ```022``` if the key is not found or its value is invalid.
+  ///
+  /// Like Hadoop, a symbolic value (e.g., ```u=rwx,g=rx,o=```) gives the permissions
+  /// to keep rather than the ones to clear.
+  pub fn umask() -> FsPermission {
+    HdfsConf::get_str("fs.permissions.umask-mode")
+      .and_then(|val| parse_umask(val.trim()))
+      .unwrap_or(FsPermission::from_mode(0o022))
+  }
+}
+
+/// Parse an octal or symbolic umask.
+fn parse_umask(val: &str) -> Option<FsPermission> {
+  if val.chars().all(|c| c.is_digit(8)) {
+    FsPermission::from_octal(val).ok()
+  } else {
+    FsPermission::from_mode(0).apply_symbolic(val, true).ok()
+      .map(|allowed| FsPermission::from_mode(!allowed.mode() & 0o777))
+  }
+}
+
+/// Parse a size like ```134217728```, ```128m``` or ```1G```.
+fn parse_bytes(val: &str) -> Option<u64> {
+  let val = val.trim();
+  let (digits, shift) = match val.chars().last() {
+    Some(c) if c.is_alphabetic() => {
+      let shift = match c.to_ascii_lowercase() {
+        'k' => 10,
+        'm' => 20,
+        'g' => 30,
+        't' => 40,
+        'p' => 50,
+        'e' => 60,
+        _ => return None
+      };
+      (&val[..val.len() - 1], shift)
+    },
+    _ => (val, 0)
+  };
+
+  digits.parse::<u64>().ok().and_then(|num| num.checked_mul(1 << shift))
+}
+
+#[cfg(test)]
+mod test {
+  use native::MiniDfsConf;
+  use minidfs::*;
+  use dfs::HdfsFsCache;
+  use perm::FsPermission;
+  use super::{parse_bytes, parse_umask, HdfsConf};
+
+  #[test]
+  fn test_parse_bytes() {
+    assert_eq!(Some(134217728), parse_bytes("134217728"));
+    assert_eq!(Some(128 * 1024 * 1024), parse_bytes("128m"));
+    assert_eq!(Some(1024 * 1024 * 1024), parse_bytes(" 1G "));
+    assert_eq!(Some(4096), parse_bytes("4k"));
+    assert_eq!(None, parse_bytes("128x"));
+    assert_eq!(None, parse_bytes("m"));
+    assert_eq!(None, parse_bytes("-1"));
+    assert_eq!(None, parse_bytes("16e"));
+  }
+
+  #[test]
+  fn test_parse_umask() {
+    assert_eq!(Some(FsPermission::from_mode(0o022)), parse_umask("022"));
+    assert_eq!(Some(FsPermission::from_mode(0o027)), parse_umask("u=rwx,g=rx,o="));
+    assert_eq!(None, parse_umask("u=rwz"));
+  }
+
+  #[test]
+  fn test_conf() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let cache = HdfsFsCache::new();
+    // keys of hdfs-default.xml are visible once connected
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+    assert!(fs.exist("/"));
+
+    let replication = HdfsConf::get_str("dfs.replication").unwrap();
+    let replication = replication.trim().parse::<i32>().unwrap();
+    assert!(replication > 0);
+    assert_eq!(Some(replication), HdfsConf::get_int("dfs.replication"));
+    assert_eq!(Some(replication as i16), HdfsConf::replication());
+
+    let block_size = HdfsConf::get_bytes("dfs.blocksize").unwrap();
+    assert!(block_size > 0);
+    assert_eq!(Some(block_size), HdfsConf::block_size());
+
+    assert_eq!(None, HdfsConf::get_str("rust.hdfs.test.missing"));
+    assert_eq!(None, HdfsConf::get_int("rust.hdfs.test.missing"));
+    assert_eq!(None, HdfsConf::get_bytes("rust.hdfs.test.missing"));
+    assert_eq!(None, HdfsConf::get_int("nul\0key"));
+
+    let umask = HdfsConf::get_str("fs.permissions.umask-mode")
+      .and_then(|val| parse_umask(val.trim()))
+      .unwrap_or(FsPermission::from_mode(0o022));
+    assert_eq!(umask, HdfsConf::umask());
+
+    dfs.stop();
+  }
+}
@@ -1,22 +1,43 @@
+use std::cmp;
 use std::collections::HashMap;
-use std::marker::PhantomData;
+use std::ffi::CString;
+#[cfg(unix)] use std::ffi::OsStr;
+use std::i32;
+use std::i64;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::mem;
-use std::rc::Rc;
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::ptr;
 use std::slice;
+use std::str::{self, Utf8Error};
 use std::string::String;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "hadoop2_7")] use std::thread;
+use std::time::SystemTime;
+#[cfg(feature = "hadoop2_7")] use std::time::{Duration, Instant};
 
 use url::{UrlParser,SchemeType};
-use libc::{c_char, c_int, c_short, c_void, int16_t, int32_t, int64_t, time_t};
+use libc::{c_char, c_int, c_short, c_void, int16_t, int32_t, int64_t, size_t};
 
+use conf::HdfsConf;
 use err::HdfsErr;
+use glob;
 use native::*;
-use util::{chars_to_str, str_to_chars, bool_to_c_int};
+use path::HdfsPath;
+use perm::FsPermission;
+use ranges::{self, RangeBuffers, ReadRangesOptions};
+use walk::Walk;
+use util::{bytes_to_cstring, chars_to_bytes, chars_to_string, to_cstring, bool_to_c_int,
+  system_time_to_time_t, temp_path, time_t_to_system_time};
 
 const O_RDONLY: c_int = 0;
 const O_WRONLY: c_int = 1;
 const O_APPEND: c_int = 1024;
 
+/// The maximum path length of HDFS (```dfs.namenode.fs-limits.max-path-length```)
+const MAX_PATH_LEN: usize = 8000;
+
 /// Options for zero-copy read
 pub struct RzOptions {
   ptr: *const hadoopRzOptions
@@ -41,21 +62,23 @@ impl RzOptions {
     if res == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(HdfsErr::last_os_error("skip_checksum", ""))
     }
   }
 
   pub fn set_bytebuffer_pool(&self, class_name: &str)
       -> Result<bool, HdfsErr> {
 
+    let c_class_name = try!(CString::new(class_name).map_err(|_|
+      HdfsErr::InvalidArgument(format!("class name with a NUL byte: {:?}", class_name))));
     let res = unsafe {
-      hadoopRzOptionsSetByteBufferPool(self.ptr, str_to_chars(class_name))
+      hadoopRzOptionsSetByteBufferPool(self.ptr, c_class_name.as_ptr())
     };
 
     if res == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(HdfsErr::last_os_error("set_bytebuffer_pool", class_name))
     }
   }
 }
@@ -63,7 +86,7 @@ impl RzOptions {
 /// A buffer returned from zero-copy read.
 /// This buffer will be automatically freed when its lifetime is finished.
 pub struct RzBuffer<'a> {
-  file: &'a HdfsFile<'a>,
+  file: &'a HdfsFile,
   ptr: *const hadoopRzBuffer
 }
 
@@ -88,6 +111,7 @@ impl<'a> RzBuffer<'a> {
     if !ptr.is_null() {
       Ok( ptr as *const u8 )
      } else {
+      // libhdfs returns NULL data at the end of file
       Err(HdfsErr::Unknown)
      }
   }
@@ -110,8 +134,8 @@ impl<'a> RzBuffer<'a> {
   }
 }
 
-/// Includes hostnames where a particular block of a file is stored.
-pub struct BlockHosts {
+/// Safely deallocable array of block hosts returned from hdfsGetHosts
+struct BlockHosts {
   ptr: *const *const *const c_char
 }
 
@@ -121,6 +145,34 @@ impl Drop for BlockHosts {
   }
 }
 
+/// Location of a block of a file, including hostnames where its replicas are stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockLocation {
+  offset: u64,
+  len: u64,
+  hosts: Vec<String>
+}
+
+impl BlockLocation {
+  /// Get the offset of the block in the file, in bytes.
+  #[inline]
+  pub fn offset(&self) -> u64 {
+    self.offset
+  }
+
+  /// Get the length of the block, in bytes.
+  #[inline]
+  pub fn len(&self) -> u64 {
+    self.len
+  }
+
+  /// Get hostnames where the replicas of the block are stored.
+  #[inline]
+  pub fn hosts(&self) -> &[String] {
+    &self.hosts
+  }
+}
+
 /// Safely deallocable hdfsFileInfo pointer
 struct HdfsFileInfoPtr {
   pub ptr: *const hdfsFileInfo,
@@ -128,7 +180,7 @@ struct HdfsFileInfoPtr {
 }
 
 /// for safe deallocation
-impl<'a> Drop for HdfsFileInfoPtr {
+impl Drop for HdfsFileInfoPtr {
   fn drop(&mut self) {
     unsafe { hdfsFreeFileInfo(self.ptr, self.len) };
   }
@@ -148,144 +200,342 @@ impl HdfsFileInfoPtr {
       len: len
     }
   }
+
+  /// Copy all entries into owned FileStatus values.
+  fn to_file_statuses(&self) -> Vec<FileStatus> {
+    (0..self.len)
+      .map(|idx| FileStatus::from_raw(unsafe { self.ptr.offset(idx as isize) }))
+      .collect()
+  }
+}
+
+/// A string from libhdfs, converted lossily. The original bytes are kept
+/// only if they are not valid UTF-8.
+fn lossy_string(bytes: Vec<u8>) -> (String, Option<Vec<u8>>) {
+  match String::from_utf8(bytes) {
+    Ok(s) => (s, None),
+    Err(e) => {
+      let bytes = e.into_bytes();
+      (String::from_utf8_lossy(&bytes).into_owned(), Some(bytes))
+    }
+  }
 }
 
 /// Interface that represents the client side information for a file or directory.
-pub struct FileStatus<'a> {
-  raw: Rc<HdfsFileInfoPtr>,
-  idx: u32,
-  _marker: PhantomData<&'a ()>
+///
+/// It owns all of its fields, so it can be kept or sent to other threads
+/// after the native hdfsFileInfo is freed.
+///
+/// Names, owners and groups are not guaranteed to be valid UTF-8. ```name()```,
+/// ```owner()``` and ```group()``` replace invalid sequences with ```U+FFFD```, while
+/// ```name_bytes()``` and the ```try_``` accessors give the exact values.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FileStatus {
+  name: String,
+  /// the original bytes of a name which is not valid UTF-8
+  name_raw: Option<Vec<u8>>,
+  is_directory: bool,
+  len: u64,
+  block_size: u64,
+  replication: i16,
+  owner: String,
+  owner_raw: Option<Vec<u8>>,
+  group: String,
+  group_raw: Option<Vec<u8>>,
+  permission: FsPermission,
+  modified: SystemTime,
+  accessed: SystemTime,
+  encrypted: bool
 }
 
-impl<'a> FileStatus<'a> {
-  #[inline]
-  /// create FileStatus from *const hdfsFileInfo
-  fn new(ptr: *const hdfsFileInfo) -> FileStatus<'a> {
+impl FileStatus {
+  /// create FileStatus by copying *const hdfsFileInfo
+  fn from_raw(ptr: *const hdfsFileInfo) -> FileStatus {
+    let info = unsafe { &*ptr };
+    let (name, name_raw) = lossy_string(chars_to_bytes(info.mName));
+    let (owner, owner_raw) = lossy_string(chars_to_bytes(info.mOwner));
+    let (group, group_raw) = lossy_string(chars_to_bytes(info.mGroup));
+
     FileStatus {
-      raw: Rc::new(HdfsFileInfoPtr::new(ptr)),
-      idx: 0,
-      _marker: PhantomData
+      name: name,
+      name_raw: name_raw,
+      is_directory: match info.mKind {
+        tObjectKind::kObjectKindFile => false,
+        tObjectKind::kObjectKindDirectory => true,
+      },
+      len: info.mSize as u64,
+      block_size: info.mBlockSize as u64,
+      replication: info.mReplication as i16,
+      owner: owner,
+      owner_raw: owner_raw,
+      group: group,
+      group_raw: group_raw,
+      permission: FsPermission::from_mode(info.mPermissions as u16),
+      modified: time_t_to_system_time(info.mLastMod),
+      accessed: time_t_to_system_time(info.mLastAccess),
+      // -1 means that libhdfs cannot tell it.
+      encrypted: unsafe { hdfsFileIsEncrypted(ptr) } == 1
     }
   }
   
-  /// create FileStatus from *const hdfsFileInfo which points 
-  /// to dynamically allocated array.
+  /// Get the name of the file. Invalid UTF-8 sequences are replaced with ```U+FFFD```.
   #[inline]
-  fn from_array(raw: Rc<HdfsFileInfoPtr>, idx: u32) -> FileStatus<'a> {
-    FileStatus {
-      raw: raw,
-      idx: idx,
-      _marker: PhantomData
-    }
+  pub fn name(&self) -> &str 
+  { 
+    &self.name
   }
-  
-  #[inline]
-  fn ptr(&self) -> *const hdfsFileInfo {
-    unsafe {self.raw.ptr.offset(self.idx as isize)}
+
+  /// Get the name of the file as it is stored in HDFS
+  pub fn name_bytes(&self) -> &[u8]
+  {
+    self.name_raw.as_ref().map_or(self.name.as_bytes(), |raw| &raw[..])
   }
-  
-  /// Get the name of the file
-  #[inline]
-  pub fn name(&self) -> &'a str 
-  { 
-    chars_to_str(unsafe {&*self.ptr()}.mName) 
+
+  /// Get the name of the file, or an error if it is not valid UTF-8
+  pub fn try_name(&self) -> Result<&str, Utf8Error>
+  {
+    str::from_utf8(self.name_bytes())
+  }
+
+  /// Get the name of the file as an ```OsStr```
+  #[cfg(unix)]
+  pub fn name_os(&self) -> &OsStr
+  {
+    OsStr::from_bytes(self.name_bytes())
+  }
+
+  /// Get the fully qualified path of the file
+  pub fn path(&self) -> HdfsPath {
+    HdfsPath::new(&self.name)
   }
   
   /// Is this a file?
   #[inline]
   pub fn is_file(&self) -> bool {
-    match unsafe {&*self.ptr()}.mKind {
-      tObjectKind::kObjectKindFile => true,
-      tObjectKind::kObjectKindDirectory => false,
-    }
+    !self.is_directory
   }
   
   /// Is this a directory?
   #[inline]
   pub fn is_directory(&self) -> bool {
-    match unsafe {&*self.ptr()}.mKind {
-      tObjectKind::kObjectKindFile => false,
-      tObjectKind::kObjectKindDirectory => true,
-    }
+    self.is_directory
   }
   
-  /// Get the owner of the file
+  /// Get the owner of the file. Invalid UTF-8 sequences are replaced with ```U+FFFD```.
   #[inline]
-  pub fn owner(&self) -> &'a str
+  pub fn owner(&self) -> &str
+  {
+    &self.owner
+  }
+
+  /// Get the owner of the file as it is stored in HDFS
+  pub fn owner_bytes(&self) -> &[u8]
+  {
+    self.owner_raw.as_ref().map_or(self.owner.as_bytes(), |raw| &raw[..])
+  }
+
+  /// Get the owner of the file, or an error if it is not valid UTF-8
+  pub fn try_owner(&self) -> Result<&str, Utf8Error>
   {
-    chars_to_str(unsafe {&*self.ptr()}.mOwner)
+    str::from_utf8(self.owner_bytes())
   }
   
-  /// Get the group associated with the file
+  /// Get the group associated with the file. Invalid UTF-8 sequences are
+  /// replaced with ```U+FFFD```.
   #[inline]
-  pub fn group(&self) -> &'a str
+  pub fn group(&self) -> &str
+  {
+    &self.group
+  }
+
+  /// Get the group associated with the file as it is stored in HDFS
+  pub fn group_bytes(&self) -> &[u8]
+  {
+    self.group_raw.as_ref().map_or(self.group.as_bytes(), |raw| &raw[..])
+  }
+
+  /// Get the group associated with the file, or an error if it is not valid UTF-8
+  pub fn try_group(&self) -> Result<&str, Utf8Error>
   {
-    chars_to_str(unsafe {&*self.ptr()}.mGroup)
+    str::from_utf8(self.group_bytes())
   }
   
   /// Get the permissions associated with the file
   #[inline]
-  pub fn permission(&self) -> i16
+  pub fn permission(&self) -> FsPermission
   {
-    unsafe {&*self.ptr()}.mPermissions as i16
+    self.permission
   }
   
   /// Get the length of this file, in bytes.
   #[inline]
   pub fn len(&self) -> usize 
   {
-    unsafe {&*self.ptr()}.mSize as usize
+    self.len as usize
   }
   
   /// Get the block size of the file.
   #[inline]
   pub fn block_size(&self) -> usize
   {
-    unsafe {&*self.ptr()}.mBlockSize as usize
+    self.block_size as usize
   }
   
   /// Get the replication factor of a file.
   #[inline]
   pub fn replica_count(&self) -> i16
   {
-    unsafe {&*self.ptr()}.mReplication as i16
+    self.replication
   }
-  
-  /// Get the last modification time for the file in seconds
+
+  /// Get the last modification time for the file
   #[inline]
-  pub fn last_modified(&self) -> time_t
+  pub fn modified(&self) -> SystemTime
   {
-    unsafe {&*self.ptr()}.mLastMod
+    self.modified
   }
-  
-  /// Get the last access time for the file in seconds
+
+  /// Get the last access time for the file
+  #[inline]
+  pub fn accessed(&self) -> SystemTime
+  {
+    self.accessed
+  }
+
+  /// Is the file in an encryption zone?
   #[inline]
-  pub fn last_accced(&self) -> time_t
+  pub fn is_encrypted(&self) -> bool
   {
-    unsafe {&*self.ptr()}.mLastAccess
+    self.encrypted
+  }
+}
+
+/// Summary of a directory tree, like ```hdfs dfs -count``` and ```hdfs dfs -du```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContentSummary {
+  /// the total length of files, in bytes
+  pub length: u64,
+  /// the number of files
+  pub file_count: u64,
+  /// the number of directories, including the root of the tree
+  pub directory_count: u64,
+  /// the raw space consumed by files, i.e., the sum of length × replication
+  pub space_consumed: u64
+}
+
+impl ContentSummary {
+  fn add(&mut self, status: &FileStatus) {
+    if status.is_directory() {
+      self.directory_count += 1;
+    } else {
+      self.file_count += 1;
+      self.length += status.len() as u64;
+      self.space_consumed += status.len() as u64 * status.replica_count() as u64;
+    }
+  }
+}
+
+/// A connection shared by all clones of HdfsFs. It is disconnected when the
+/// last clone is dropped.
+struct HdfsFsInner {
+  url: String,
+  raw: *const hdfsFS,
+  /// set when a call fails in a way that the connection may be broken
+  suspect: AtomicBool
+}
+
+// hdfsFS of libhdfs is thread-safe.
+unsafe impl Send for HdfsFsInner {}
+unsafe impl Sync for HdfsFsInner {}
+
+impl HdfsFsInner {
+  fn disconnect(&mut self) -> Result<bool, HdfsErr> {
+    if self.raw.is_null() {
+      return Ok(true);
+    }
+
+    info!("Disconnecting from Namenode ({})", &self.url);
+    let res = unsafe { hdfsDisconnect(self.raw) };
+    // the native resources are freed even on error
+    self.raw = ptr::null();
+
+    if res == 0 {
+      Ok(true)
+    } else {
+      Err(HdfsErr::last_os_error("disconnect", ""))
+    }
+  }
+}
+
+impl Drop for HdfsFsInner {
+  fn drop(&mut self) {
+    if let Err(e) = self.disconnect() {
+      error!("{}", e);
+    }
   }
 }
 
 /// Hdfs Filesystem
 ///
 /// It is basically thread safe because the native API for hdfsFs is thread-safe. 
+/// Cloning it is cheap, and all clones share the same connection, so it can be
+/// freely sent to and shared by other threads.
+/// Relative paths are resolved against the working directory (see ```set_working_dir```).
 #[derive(Clone)]
-#[allow(raw_pointer_derive)]
-pub struct HdfsFs<'a> {
-  url: String,
-  raw: *const hdfsFS,
-  _marker: PhantomData<&'a ()>
+pub struct HdfsFs {
+  inner: Arc<HdfsFsInner>
 }
 
-impl<'a> HdfsFs<'a> {
+impl HdfsFs {
   /// create HdfsFs instance. Please use HdfsFsCache rather than using this API directly. 
   #[inline]
-  fn new(url: String, raw: *const hdfsFS) -> HdfsFs<'a>
+  fn new(url: String, raw: *const hdfsFS) -> HdfsFs
   {
     HdfsFs {
-      url: url,
-      raw: raw,
-      _marker: PhantomData
+      inner: Arc::new(HdfsFsInner {
+        url: url,
+        raw: raw,
+        suspect: AtomicBool::new(false)
+      })
+    }
+  }
+
+  /// Disconnect from the namenode if this is the last handle of the connection,
+  /// and return ```Ok(true)```. Otherwise, it returns ```Ok(false)```, and the
+  /// connection is closed when the other handles (e.g., the one kept by
+  /// ```HdfsFsCache```) are dropped.
+  pub fn disconnect(self) -> Result<bool, HdfsErr> {
+    match Arc::try_unwrap(self.inner) {
+      Ok(mut inner) => inner.disconnect(),
+      Err(_) => Ok(false)
+    }
+  }
+
+  /// Build an error from errno, and remember that the connection may be broken
+  /// so that HdfsFsCache checks it before handing it out again. It may be broken
+  /// on a connection error or ```EINTERNAL```, which libhdfs reports for network
+  /// failures as well as any other unmapped Java exception. ```is_alive``` tells
+  /// them apart.
+  fn last_error(&self, op: &'static str, path: &str) -> HdfsErr {
+    let err = HdfsErr::last_os_error(op, path);
+    if err.is_connection_error() || err.errno() == Some(EINTERNAL) {
+      self.inner.suspect.store(true, Ordering::Relaxed);
+    }
+    err
+  }
+
+  /// Check if the connection works, if any call has failed in a way that
+  /// the connection may be broken. It costs a single cheap call only then.
+  fn is_alive(&self) -> bool {
+    if !self.inner.suspect.load(Ordering::Relaxed) {
+      return true;
+    }
+
+    self.inner.suspect.store(false, Ordering::Relaxed);
+    match self.get_file_status("/") {
+      Ok(_) => true,
+      Err(e) => !(e.is_connection_error() || e.errno() == Some(EINTERNAL))
     }
   }
   
@@ -293,168 +543,484 @@ impl<'a> HdfsFs<'a> {
   #[inline]
   pub fn url(&self) -> &str
   {
-    &self.url
+    &self.inner.url
   }
   
   /// Get a raw pointer of JNI API's HdfsFs
   #[inline]
   pub fn raw(&self) -> *const hdfsFS
   {
-    self.raw
+    self.inner.raw
   }
 
-  /// Open a file for append
-  pub fn append(&self, path: &str) -> Result<HdfsFile, HdfsErr> {
-    if !self.exist(path) {
-      return Err(HdfsErr::FileNotFound(path.to_owned()));
-    }
+  /// Get the current working directory as a fully qualified path.
+  pub fn working_dir(&self) -> Result<String, HdfsErr> {
+    // one more byte than given to libhdfs keeps the path null-terminated
+    let mut buf: Vec<u8> = vec![0; MAX_PATH_LEN + 1];
 
-    let file = unsafe {
-      hdfsOpenFile(self.raw, str_to_chars(path), O_APPEND, 0,0,0)
+    let ptr = unsafe {
+      hdfsGetWorkingDirectory(self.raw(), buf.as_mut_ptr() as *mut c_char,
+        MAX_PATH_LEN as size_t)
     };
 
-    if file.is_null() {
-      Err(HdfsErr::Unknown)
+    if ptr.is_null() {
+      Err(self.last_error("working_dir", ""))
+    } else {
+      Ok(chars_to_string(ptr))
+    }
+  }
+
+  /// Set the working directory. All relative paths given to HdfsFs and
+  /// HdfsFile APIs are resolved against it.
+  ///
+  /// The working directory belongs to the connection, so it is shared by
+  /// every HdfsFs obtained from HdfsFsCache for the same namenode.
+  pub fn set_working_dir(&self, path: &str) -> Result<bool, HdfsErr> {
+    let c_path = try!(to_cstring(path));
+    if unsafe { hdfsSetWorkingDirectory(self.raw(), c_path.as_ptr()) } == 0 {
+      Ok(true)
+    } else {
+      Err(self.last_error("set_working_dir", path))
+    }
+  }
+
+  /// Resolve a relative path against the working directory. Absolute paths
+  /// and fully qualified paths are returned as they are.
+  fn absolute_path(&self, path: &str) -> Result<String, HdfsErr> {
+    if path.starts_with("/") || HdfsPath::new(path).is_qualified() {
+      Ok(path.to_owned())
+    } else {
+      let mut abs = try!(self.working_dir());
+      if !abs.ends_with("/") {
+        abs.push('/');
+      }
+      abs.push_str(path);
+      Ok(abs)
+    }
+  }
+
+  /// Get the fully qualified path of ```path```. Relative paths are resolved
+  /// against the working directory.
+  pub fn qualify(&self, path: &str) -> Result<HdfsPath, HdfsErr> {
+    let path = HdfsPath::new(path);
+
+    if path.is_absolute() {
+      Ok(path.qualify(self.url()))
     } else {
-      Ok(HdfsFile {fs: self, path: path.to_owned(), file: file})
+      Ok(path.qualify(&try!(self.working_dir())))
     }
   }
 
+  /// Open a file for append
+  #[inline]
+  pub fn append(&self, path: &str) -> Result<HdfsFile, HdfsErr> {
+    self.open_with_options(path, OpenOptions::new().set_append(true))
+  }
+
   /// set permission
-  pub fn chmod(&self, path: &str, mode: i16) -> bool {
-    (unsafe {
-      hdfsChmod(self.raw, str_to_chars(path), mode as c_short)}) == 0
+  pub fn chmod(&self, path: &str, perm: FsPermission) -> Result<bool, HdfsErr> {
+    let c_path = try!(to_cstring(path));
+    let res = unsafe {
+      hdfsChmod(self.raw(), c_path.as_ptr(), perm.mode() as c_short)
+    };
+
+    if res == 0 {
+      Ok(true)
+    } else {
+      Err(self.last_error("chmod", path))
+    }
   }
 
-  pub fn chown(&self, path: &str, owner: &str, group: &str) -> bool {
-    (unsafe {
-      hdfsChown(self.raw, str_to_chars(path),
-        str_to_chars(owner), str_to_chars(group))}) == 0
+  /// Change the owner and the group of a file or directory.
+  pub fn chown(&self, path: &str, owner: &str, group: &str)
+      -> Result<bool, HdfsErr> {
+
+    let c_path = try!(to_cstring(path));
+    let c_owner = try!(to_cstring(owner));
+    let c_group = try!(to_cstring(group));
+    let res = unsafe {
+      hdfsChown(self.raw(), c_path.as_ptr(),
+        c_owner.as_ptr(), c_group.as_ptr())
+    };
+
+    if res == 0 {
+      Ok(true)
+    } else {
+      Err(self.last_error("chown", path))
+    }
   }
 
+  /// Create a file. It atomically fails with ```FileAlreadyExists``` if the file
+  /// exists, which requires append support (see ```OpenOptions```).
   #[inline]
   pub fn create(&self, path: &str) -> Result<HdfsFile, HdfsErr> {
-    self.create_with_params(path, false, 0, 0, 0)
+    self.open_with_options(path, OpenOptions::new().set_write(true).set_create(true))
   }
 
+  /// Create a file, replacing an existing file if ```overwrite``` is true.
   #[inline]
   pub fn create_with_overwrite(&self, path: &str,
     overwrite: bool) -> Result<HdfsFile, HdfsErr> {
 
-    self.create_with_params(path, overwrite, 0, 0, 0)
+    self.open_with_options(path,
+      OpenOptions::new().set_write(true).set_create(true).set_overwrite(overwrite))
+  }
+
+  /// Create a file whose permission is ```perm``` masked by the configured umask.
+  ///
+  /// libhdfs cannot pass a permission on creation, so the permission is set
+  /// right after the file is created.
+  pub fn create_with_permission(&self, path: &str, perm: FsPermission)
+      -> Result<HdfsFile, HdfsErr> {
+
+    self.open_with_options(path,
+      OpenOptions::new().set_write(true).set_create(true).set_permission(perm))
   }
 
+  #[deprecated(since = "0.0.5", note = "use open_with_options")]
   pub fn create_with_params(
-    &'a self,
+    &self,
     path: &str,
     overwrite: bool,
     buf_size: i32,
     replica_num: i16,
     block_size: i32) -> Result<HdfsFile, HdfsErr> {
 
-    if !overwrite && self.exist(path) {
-      return Err(HdfsErr::FileAlreadyExists(path.to_owned()));
+    self.open_with_options(path, OpenOptions::new()
+      .set_write(true)
+      .set_create(true)
+      .set_overwrite(overwrite)
+      .set_buffer_size(buf_size)
+      .set_replication(replica_num)
+      .set_block_size(cmp::max(block_size, 0) as u64))
+  }
+
+  /// Open a file as ```opts``` describes. See ```OpenOptions``` for the
+  /// supported combinations.
+  pub fn open_with_options(&self, path: &str, opts: &OpenOptions)
+      -> Result<HdfsFile, HdfsErr> {
+
+    // The file keeps an absolute path so that changing the working directory
+    // later does not change the file it refers to.
+    let path = &try!(self.absolute_path(path));
+
+    if opts.read {
+      if opts.write || opts.append || opts.create || opts.create_new {
+        return Err(HdfsErr::InvalidArgument(
+          "HDFS cannot read and write a file at once".to_owned()));
+      }
+      return self.open_file(path, O_RDONLY, opts, "open");
+    }
+
+    if opts.append {
+      if opts.create_new {
+        return self.create_exclusive(path, opts);
+      }
+
+      return match self.open_file(path, O_WRONLY | O_APPEND, opts, "append") {
+        Err(ref e) if opts.create && e.is_not_found() => {
+          match self.create_exclusive(path, opts) {
+            // created by someone else in the meantime
+            Err(ref e) if e.is_already_exists() =>
+              self.open_file(path, O_WRONLY | O_APPEND, opts, "append"),
+            res => res
+          }
+        },
+        res => res
+      };
+    }
+
+    if !opts.write {
+      return Err(HdfsErr::InvalidArgument("neither read, write nor append is set".to_owned()));
+    }
+
+    if opts.create_new || (opts.create && !opts.overwrite) {
+      self.create_exclusive(path, opts)
+    } else if opts.create {
+      self.create_file(path, opts)
+    } else if opts.overwrite {
+      // only an existing file is overwritten
+      try!(self.get_file_status(path));
+      self.create_file(path, opts)
+    } else {
+      Err(HdfsErr::InvalidArgument(
+        "writing requires create, create_new, overwrite or append".to_owned()))
     }
+  }
+
+  /// Call hdfsOpenFile, or an hdfsStreamBuilder if the block size does not fit in 32 bits.
+  fn open_file(&self, path: &str, flags: c_int, opts: &OpenOptions, op: &'static str)
+      -> Result<HdfsFile, HdfsErr> {
+
+    let c_path = try!(to_cstring(path));
 
-    let file = unsafe {
-      hdfsOpenFile(self.raw, str_to_chars(path), O_WRONLY,
-        buf_size as c_int, replica_num as c_short, block_size as int32_t)
+    let file = if opts.block_size > i32::MAX as u64 {
+      try!(self.open_with_stream_builder(&c_path, path, flags, opts, op))
+    } else {
+      unsafe {
+        hdfsOpenFile(self.raw(), c_path.as_ptr(), flags, opts.buffer_size as c_int,
+          opts.replication as c_short, opts.block_size as int32_t)
+      }
     };
 
     if file.is_null() {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error(op, path))
     } else {
-      Ok(HdfsFile {fs: self, path: path.to_owned(), file: file})
+      Ok(HdfsFile {fs: self.clone(), path: path.to_owned(), file: file})
+    }
+  }
+
+  #[cfg(feature = "hadoop3")]
+  fn open_with_stream_builder(&self, c_path: &CString, path: &str, flags: c_int,
+      opts: &OpenOptions, op: &'static str) -> Result<*const hdfsFile, HdfsErr> {
+
+    unsafe {
+      let bld = hdfsStreamBuilderAlloc(self.raw(), c_path.as_ptr(), flags);
+      if bld.is_null() {
+        return Ok(ptr::null());
+      }
+
+      if (opts.buffer_size > 0 &&
+            hdfsStreamBuilderSetBufferSize(bld, opts.buffer_size) != 0) ||
+          (opts.replication > 0 &&
+            hdfsStreamBuilderSetReplication(bld, opts.replication) != 0) ||
+          hdfsStreamBuilderSetDefaultBlockSize(bld, opts.block_size as int64_t) != 0 {
+        let err = self.last_error(op, path);
+        hdfsStreamBuilderFree(bld);
+        return Err(err);
+      }
+
+      Ok(hdfsStreamBuilderBuild(bld))
+    }
+  }
+
+  #[cfg(not(feature = "hadoop3"))]
+  fn open_with_stream_builder(&self, _: &CString, _: &str, _: c_int,
+      opts: &OpenOptions, _: &'static str) -> Result<*const hdfsFile, HdfsErr> {
+
+    Err(HdfsErr::InvalidArgument(format!(
+      "block size {} requires the hadoop3 feature", opts.block_size)))
+  }
+
+  /// Create or overwrite a file, and set its permission if given.
+  fn create_file(&self, path: &str, opts: &OpenOptions) -> Result<HdfsFile, HdfsErr> {
+    let file = try!(self.open_file(path, O_WRONLY, opts, "create"));
+    if let Some(perm) = opts.permission {
+      try!(self.chmod(path, perm.apply_umask(HdfsConf::umask())));
+    }
+    Ok(file)
+  }
+
+  /// Create a file only if it does not exist, atomically.
+  ///
+  /// libhdfs always overwrites existing files, so an empty file is created under
+  /// a temporary name and renamed to ```path```, which fails in HDFS if ```path```
+  /// exists. Then, the file is opened for append, so the filesystem must support
+  /// append.
+  fn create_exclusive(&self, path: &str, opts: &OpenOptions) -> Result<HdfsFile, HdfsErr> {
+    if self.exist(path) {
+      return Err(HdfsErr::FileAlreadyExists(path.to_owned()));
+    }
+
+    let tmp_path = &temp_path(path);
+    let created = self.create_file(tmp_path, opts).and_then(|file| file.close());
+    if let Err(e) = created {
+      let _ = self.delete(tmp_path, false);
+      return Err(e);
+    }
+
+    if let Err(e) = self.rename_exclusive(tmp_path, path) {
+      let _ = self.delete(tmp_path, false);
+      return Err(e);
+    }
+
+    self.open_file(path, O_WRONLY | O_APPEND, opts, "create")
+  }
+
+  /// Rename a file, failing with ```FileAlreadyExists``` if ```new_path``` exists.
+  fn rename_exclusive(&self, old_path: &str, new_path: &str) -> Result<bool, HdfsErr> {
+    if let Err(e) = self.rename(old_path, new_path) {
+      return Err(if self.exist(new_path) {
+        HdfsErr::FileAlreadyExists(new_path.to_owned())
+      } else {
+        e
+      });
+    }
+
+    // renaming to a directory created in the meantime moves the file into it
+    if try!(self.get_file_status(new_path)).is_directory() {
+      let moved = format!("{}/{}", new_path, &old_path[old_path.rfind('/').unwrap() + 1..]);
+      let _ = self.delete(&moved, false);
+      return Err(HdfsErr::FileAlreadyExists(new_path.to_owned()));
     }
+
+    Ok(true)
+  }
+
+  /// Create a file which appears at ```path``` only when it is committed.
+  /// See ```AtomicWriter```.
+  pub fn create_atomic(&self, path: &str, overwrite: bool) -> Result<AtomicWriter, HdfsErr> {
+    let path = try!(self.absolute_path(path));
+    let tmp_path = temp_path(&path);
+    let file = try!(self.open_with_options(&tmp_path,
+      OpenOptions::new().set_write(true).set_create(true).set_overwrite(true)));
+
+    Ok(AtomicWriter {
+      file: Some(file),
+      path: path,
+      tmp_path: tmp_path,
+      overwrite: overwrite
+    })
   }
 
   /// Get the default blocksize.
   pub fn default_blocksize(&self) -> Result<usize, HdfsErr> {
-    let block_sz = unsafe { hdfsGetDefaultBlockSize(self.raw) };
+    let block_sz = unsafe { hdfsGetDefaultBlockSize(self.raw()) };
 
     if block_sz > 0 {
       Ok(block_sz as usize)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error("default_blocksize", ""))
     }
   }
 
   /// Get the default blocksize at the filesystem indicated by a given path.
   pub fn block_size(&self, path: &str) -> Result<usize, HdfsErr> {
+    let c_path = try!(to_cstring(path));
     let block_sz = unsafe {
-      hdfsGetDefaultBlockSizeAtPath(self.raw, str_to_chars(path))
+      hdfsGetDefaultBlockSizeAtPath(self.raw(), c_path.as_ptr())
     };
 
     if block_sz > 0 {
       Ok(block_sz as usize)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error("block_size", path))
     }
   }
 
   /// Return the raw capacity of the filesystem.
   pub fn capacity(&self) -> Result<usize, HdfsErr> {
-    let block_sz = unsafe {
-      hdfsGetCapacity(self.raw)
+    let capacity = unsafe {
+      hdfsGetCapacity(self.raw())
     };
 
-    if block_sz > 0 {
-      Ok(block_sz as usize)
+    if capacity >= 0 {
+      Ok(capacity as usize)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error("capacity", ""))
     }
   }
 
   /// Delete file.
   pub fn delete(&self, path: &str, recursive: bool) -> Result<bool, HdfsErr> {
+    let c_path = try!(to_cstring(path));
     let res = unsafe {
-      hdfsDelete(self.raw, str_to_chars(path), recursive as c_int)
+      hdfsDelete(self.raw(), c_path.as_ptr(), recursive as c_int)
     };
 
     if res == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error("delete", path))
     }
   }
 
   /// Checks if a given path exsits on the filesystem
+  /// It returns false for paths which libhdfs cannot take (e.g., with a NUL byte).
   pub fn exist(&self, path: &str) -> bool {
-    if unsafe {hdfsExists(self.raw, str_to_chars(path))} == 0 {
+    let c_path = match to_cstring(path) {
+      Ok(c_path) => c_path,
+      Err(_) => return false
+    };
+    if unsafe {hdfsExists(self.raw(), c_path.as_ptr())} == 0 {
       true
     } else {
       false
     }
   }
 
-  /// Get hostnames where a particular block (determined by
-  /// pos & blocksize) of a file is stored. The last element in the array
-  /// is NULL. Due to replication, a single block could be present on
-  /// multiple hosts.
+  /// Get the locations of blocks which overlap the given range of a file.
+  /// Due to replication, a single block could be present on multiple hosts.
+  ///
+  /// libhdfs reports only hostnames, so block offsets and lengths are computed
+  /// from the block size of the file. They are not correct for files whose blocks
+  /// have different sizes (e.g., files made by concat).
   pub fn get_hosts(&self, path: &str, start: usize, length: usize)
-      -> Result<BlockHosts, HdfsErr> {
+      -> Result<Vec<BlockLocation>, HdfsErr> {
+
+    let status = try!(self.get_file_status(path));
+    let file_len = status.len() as u64;
+    let block_size = status.block_size() as u64;
 
+    if block_size == 0 || length == 0 || start as u64 >= file_len {
+      return Ok(Vec::new());
+    }
+
+    let c_path = try!(to_cstring(path));
     let ptr = unsafe {
-      hdfsGetHosts(self.raw, str_to_chars(path),
+      hdfsGetHosts(self.raw(), c_path.as_ptr(),
         start as int64_t, length as int64_t)
     };
 
-    if !ptr.is_null() {
-      Ok(BlockHosts {ptr: ptr})
-    } else {
-      Err(HdfsErr::Unknown)
+    if ptr.is_null() {
+      return Err(self.last_error("get_hosts", path));
+    }
+
+    let block_hosts = BlockHosts {ptr: ptr};
+    let first_block = start as u64 / block_size;
+    let mut locations = Vec::new();
+
+    for block_idx in 0.. {
+      let hosts_ptr = unsafe { *block_hosts.ptr.offset(block_idx as isize) };
+      if hosts_ptr.is_null() {
+        break;
+      }
+
+      let mut hosts = Vec::new();
+      for host_idx in 0.. {
+        let host = unsafe { *hosts_ptr.offset(host_idx as isize) };
+        if host.is_null() {
+          break;
+        }
+        hosts.push(chars_to_string(host));
+      }
+
+      let offset = (first_block + block_idx) * block_size;
+      locations.push(BlockLocation {
+        offset: offset,
+        len: cmp::min(block_size, file_len.saturating_sub(offset)),
+        hosts: hosts
+      });
     }
+
+    Ok(locations)
+  }
+
+  /// Get the locations of all blocks of a file.
+  pub fn block_locations(&self, path: &str) -> Result<Vec<BlockLocation>, HdfsErr> {
+    let len = try!(self.get_file_status(path)).len();
+    self.get_hosts(path, 0, len)
   }
 
   /// create a directory
   pub fn mkdir(&self, path: &str) -> Result<bool, HdfsErr> {
-    if unsafe{hdfsCreateDirectory(self.raw, str_to_chars(path))} == 0 {
+    let c_path = try!(to_cstring(path));
+    if unsafe{hdfsCreateDirectory(self.raw(), c_path.as_ptr())} == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error("mkdir", path))
     }
   }
 
+  /// Create a directory whose permission is ```perm``` masked by the configured umask.
+  /// Missing parent directories are created with the default permission.
+  pub fn mkdir_with_permission(&self, path: &str, perm: FsPermission)
+      -> Result<bool, HdfsErr> {
+
+    try!(self.mkdir(path));
+    self.chmod(path, perm.apply_umask(HdfsConf::umask()))
+  }
+
   /// open a file to read
   #[inline]
   pub fn open(&self, path: &str) -> Result<HdfsFile, HdfsErr> {
@@ -462,33 +1028,93 @@ impl<'a> HdfsFs<'a> {
   }
 
   /// open a file to read with a buffer size
+  #[inline]
   pub fn open_with_bufsize(&self, path: &str, buf_size: i32)
       -> Result<HdfsFile, HdfsErr> {
 
-    let file = unsafe {
-      hdfsOpenFile(self.raw, str_to_chars(path), O_RDONLY,
-        buf_size as c_int, 0, 0)
-    };
-
-    if file.is_null() {
-      Err(HdfsErr::Unknown)
-    } else {
-      Ok(HdfsFile {fs: self, path: path.to_owned(), file: file})
-    }
+    self.open_with_options(path, OpenOptions::new().set_read(true).set_buffer_size(buf_size))
   }
 
-  /// Set the replication of the specified file to the supplied value
-  pub fn set_replication(&self, path: &str, num: i16)
-      -> Result<bool, HdfsErr> {
+  /// Truncate a file to ```new_len``` bytes, which must not be larger than the
+  /// current length. It requires the ```hadoop2_7``` feature and Hadoop 2.7 or later.
+  ///
+  /// It returns true if the file is truncated at once. It returns false if the
+  /// new length is in the middle of a block, and the last block is being recovered
+  /// in the background. Then, the file cannot be appended until the recovery
+  /// completes (see ```wait_for_truncate```).
+  #[cfg(feature = "hadoop2_7")]
+  pub fn truncate(&self, path: &str, new_len: u64) -> Result<bool, HdfsErr> {
+    if new_len > i64::MAX as u64 {
+      return Err(HdfsErr::InvalidArgument(format!("invalid length: {}", new_len)));
+    }
 
+    let c_path = try!(to_cstring(path));
     let res = unsafe {
-      hdfsSetReplication(self.raw, str_to_chars(path), num as int16_t)
+      hdfsTruncateFile(self.raw(), c_path.as_ptr(), new_len as tOffset)
     };
 
-    if res == 0 {
-      Ok(true)
-    } else {
-      Err(HdfsErr::Unknown)
+    match res {
+      1 => Ok(true),
+      0 => Ok(false),
+      _ => Err(self.last_error("truncate", path))
+    }
+  }
+
+  /// Wait until the block recovery started by ```truncate``` completes, so that
+  /// the file can be appended. It returns false if it does not complete within
+  /// ```timeout```.
+  ///
+  /// libhdfs cannot ask whether a file is closed, so it repeatedly opens the
+  /// file for append and closes it, which fails while the recovery is in progress.
+  /// Note that a successful probe updates the modification time and the
+  /// generation stamp of the last block, as an empty append does.
+  ///
+  /// libhdfs reports both a recovery in progress and a file being written by
+  /// another client as ```EINTERNAL```, so it cannot tell them apart. Call it only
+  /// right after ```truncate``` returns false, while this client holds the lease.
+  #[cfg(feature = "hadoop2_7")]
+  pub fn wait_for_truncate(&self, path: &str, timeout: Duration) -> Result<bool, HdfsErr> {
+    let path = &try!(self.absolute_path(path));
+    let c_path = try!(to_cstring(path));
+    let deadline = Instant::now() + timeout;
+    let mut interval = Duration::from_millis(100);
+
+    loop {
+      let file = unsafe {
+        hdfsOpenFile(self.raw(), c_path.as_ptr(), O_WRONLY | O_APPEND, 0, 0, 0)
+      };
+
+      if !file.is_null() {
+        return HdfsFile {fs: self.clone(), path: path.to_owned(), file: file}.close();
+      }
+
+      let err = self.last_error("wait_for_truncate", path);
+      if err.errno() != Some(EINTERNAL) {
+        return Err(err);
+      }
+
+      let now = Instant::now();
+      if now >= deadline {
+        return Ok(false);
+      }
+      thread::sleep(cmp::min(interval, deadline - now));
+      interval = cmp::min(interval * 2, Duration::from_secs(1));
+    }
+  }
+
+  /// Set the replication of the specified file to the supplied value
+  pub fn set_replication(&self, path: &str, num: i16)
+      -> Result<bool, HdfsErr> {
+
+    let c_path = try!(to_cstring(path));
+    let res = unsafe {
+      hdfsSetReplication(self.raw(), c_path.as_ptr(), num as int16_t)
+    };
+
+    if res == 0 {
+      Ok(true)
+    } else {
+      Err(self.last_error("set_replication", path))
     }
   }
 
@@ -496,106 +1122,244 @@ impl<'a> HdfsFs<'a> {
   pub fn rename(&self, old_path: &str, new_path: &str)
       -> Result<bool, HdfsErr> {
 
+    let c_old_path = try!(to_cstring(old_path));
+    let c_new_path = try!(to_cstring(new_path));
     let res = unsafe {
-      hdfsRename(self.raw, str_to_chars(old_path), str_to_chars(new_path))
+      hdfsRename(self.raw(), c_old_path.as_ptr(), c_new_path.as_ptr())
     };
 
     if res == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error("rename", old_path))
+    }
+  }
+
+  /// Set the modification time and the access time of a file or directory.
+  /// ```None``` keeps the current time. HDFS keeps times in seconds here,
+  /// so sub-second parts are truncated.
+  pub fn set_times(&self, path: &str, mtime: Option<SystemTime>,
+      atime: Option<SystemTime>) -> Result<bool, HdfsErr> {
+
+    let to_time_t = |time: Option<SystemTime>| match time {
+      Some(time) => system_time_to_time_t(time),
+      None => Ok(-1)
+    };
+    let mtime = try!(to_time_t(mtime));
+    let atime = try!(to_time_t(atime));
+
+    let c_path = try!(to_cstring(path));
+    let res = unsafe {
+      hdfsUtime(self.raw(), c_path.as_ptr(), mtime as tTime, atime as tTime)
+    };
+
+    if res == 0 {
+      Ok(true)
+    } else {
+      Err(self.last_error("set_times", path))
     }
   }
 
   /// Return the total raw size of all files in the filesystem.
   pub fn used(&self) -> Result<usize, HdfsErr> {
-    let block_sz = unsafe {
-      hdfsGetUsed(self.raw)
+    let used = unsafe {
+      hdfsGetUsed(self.raw())
     };
 
-    if block_sz > 0 {
-      Ok(block_sz as usize)
+    if used >= 0 {
+      Ok(used as usize)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error("used", ""))
     }
   }
   
   pub fn list_status(&self, path: &str) -> Result<Vec<FileStatus>, HdfsErr> {
+    self.list_status_bytes(path.as_bytes())
+  }
+
+  /// List a directory given as bytes, e.g., the ```name_bytes()``` of a
+  /// directory whose name is not valid UTF-8.
+  pub fn list_status_bytes(&self, path: &[u8]) -> Result<Vec<FileStatus>, HdfsErr> {
     let mut entry_num: c_int = 0;
     
+    let c_path = try!(bytes_to_cstring(path));
     let ptr = unsafe {
-      hdfsListDirectory(self.raw, str_to_chars(path), &mut entry_num)
+      hdfsListDirectory(self.raw(), c_path.as_ptr(), &mut entry_num)
     };
     
     if ptr.is_null() {
-      return Err(HdfsErr::Unknown)
-    }
-    
-    let shared_ptr = Rc::new(HdfsFileInfoPtr::new_array(ptr, entry_num));
-    
-    let mut list = Vec::new(); 
-    for idx in 0 .. entry_num {
-      list.push(FileStatus::from_array(shared_ptr.clone(), idx as u32));
+      return Err(self.last_error("list_status", &String::from_utf8_lossy(path)))
     }
     
-    Ok(list)
+    Ok(HdfsFileInfoPtr::new_array(ptr, entry_num).to_file_statuses())
   }    
   
+  /// Walk all entries under a directory recursively. See ```Walk``` for options.
+  pub fn walk(&self, path: &str) -> Walk {
+    Walk::new(self, path)
+  }
+
+  /// Get the content summary of a file or a directory tree.
+  ///
+  /// libhdfs provides no binding of ```getContentSummary```, so it is computed
+  /// on the client side by listing every directory in the tree.
+  pub fn content_summary(&self, path: &str) -> Result<ContentSummary, HdfsErr> {
+    let root = try!(self.get_file_status(path));
+    let mut summary = ContentSummary::default();
+    summary.add(&root);
+
+    if root.is_directory() {
+      for entry in self.walk(path) {
+        summary.add(&try!(entry));
+      }
+    }
+
+    Ok(summary)
+  }
+
+  /// Get the statuses of paths matching a glob pattern (e.g., ```/logs/*/part-*.gz```),
+  /// sorted by their names. It follows the semantics of Hadoop's
+  /// ```FileSystem.globStatus```:
+  ///
+  /// * ```?``` matches any single character.
+  /// * ```*``` matches zero or more characters.
+  /// * ```[abc]``` and ```[a-z]``` match a single character in the set or the range,
+  ///   and ```[^a]``` or ```[!a]``` match a single character not in it.
+  /// * ```{ab,cd}``` matches either of the comma-separated sub-patterns,
+  ///   which may contain slashes or nested braces.
+  /// * ```\c``` matches the character ```c``` literally.
+  ///
+  /// Wildcards never match ```/```. A path without wildcards is returned only
+  /// if it exists, so an empty vector means that nothing matches.
+  pub fn glob(&self, pattern: &str) -> Result<Vec<FileStatus>, HdfsErr> {
+    glob::glob(self, pattern)
+  }
+
   pub fn get_file_status(&self, path: &str) -> Result<FileStatus, HdfsErr> {
+    self.get_file_status_bytes(path.as_bytes())
+  }
+
+  /// Get the status of a path given as bytes, e.g., the ```name_bytes()``` of
+  /// an entry whose name is not valid UTF-8.
+  pub fn get_file_status_bytes(&self, path: &[u8]) -> Result<FileStatus, HdfsErr> {
+    let c_path = try!(bytes_to_cstring(path));
     let ptr = unsafe {
-      hdfsGetPathInfo(self.raw, str_to_chars(path))
+      hdfsGetPathInfo(self.raw(), c_path.as_ptr())
     };
     
     if ptr.is_null() {
-      Err(HdfsErr::Unknown)
+      Err(self.last_error("get_file_status", &String::from_utf8_lossy(path)))
+    } else {
+      Ok(FileStatus::from_raw(HdfsFileInfoPtr::new(ptr).ptr))
+    }
+  }
+}
+
+/// Read statistics of a file opened for read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadStatistics {
+  /// bytes read in total
+  pub total_bytes_read: u64,
+  /// bytes read from a datanode on the same host
+  pub total_local_bytes_read: u64,
+  /// bytes read through short-circuit local reads
+  pub total_short_circuit_bytes_read: u64,
+  /// bytes read through zero-copy reads
+  pub total_zero_copy_bytes_read: u64,
+  /// bytes read from datanodes on other hosts
+  pub remote_bytes_read: u64
+}
+
+impl ReadStatistics {
+  #[inline]
+  fn ratio(&self, bytes: u64) -> f64 {
+    if self.total_bytes_read == 0 {
+      0.0
     } else {
-      Ok(FileStatus::new(ptr))
+      bytes as f64 / self.total_bytes_read as f64
     }
   }
+
+  /// The fraction of bytes read locally (0 if nothing has been read)
+  pub fn local_ratio(&self) -> f64 {
+    self.ratio(self.total_local_bytes_read)
+  }
+
+  /// The fraction of bytes read through short-circuit local reads
+  /// (0 if nothing has been read)
+  pub fn short_circuit_ratio(&self) -> f64 {
+    self.ratio(self.total_short_circuit_bytes_read)
+  }
 }
 
 /// open hdfs file
-pub struct HdfsFile<'a> {
-  fs: &'a HdfsFs<'a>,
+///
+/// It implements ```std::io::Read```, ```std::io::Write``` and ```std::io::Seek```.
+/// The file is closed when it is dropped.
+///
+/// It keeps a handle of its HdfsFs, so the connection is not closed while the
+/// file is open. It does not borrow HdfsFs, and it can be moved to other threads.
+pub struct HdfsFile {
+  fs: HdfsFs,
   path: String,
   file: *const hdfsFile
 }
 
-impl<'a> HdfsFile<'a> {
+// An hdfsFile can be used by any thread, though not by multiple threads at once.
+unsafe impl Send for HdfsFile {}
+
+impl HdfsFile {
 
   pub fn available(&self) -> Result<bool, HdfsErr> {
-    if unsafe { hdfsAvailable(self.fs.raw, self.file) } == 0 {
+    if unsafe { hdfsAvailable(self.fs.raw(), self.file) } == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.fs.last_error("available", &self.path))
     }
   }
 
-  /// Close the opened file
-  pub fn close(&self) -> Result<bool, HdfsErr> {
-    if unsafe {hdfsCloseFile(self.fs.raw, self.file)} == 0 {
+  /// Close the opened file. A file is also closed when it is dropped,
+  /// but closing it explicitly is the only way to find out whether it succeeds.
+  pub fn close(mut self) -> Result<bool, HdfsErr> {
+    let res = unsafe {hdfsCloseFile(self.fs.raw(), self.file)};
+    // hdfsCloseFile frees the handle even on failure, so Drop must not close it again.
+    self.file = ptr::null();
+
+    if res == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.fs.last_error("close", &self.path))
     }
   }
 
   /// Flush the data.
-  pub fn flush(&self) -> bool {
-    (unsafe { hdfsFlush(self.fs.raw, self.file) }) == 0
+  pub fn flush(&self) -> Result<bool, HdfsErr> {
+    if unsafe { hdfsFlush(self.fs.raw(), self.file) } == 0 {
+      Ok(true)
+    } else {
+      Err(self.fs.last_error("flush", &self.path))
+    }
   }
 
   /// Flush out the data in client's user buffer. After the return of this
   /// call, new readers will see the data.
-  pub fn hflush(&self) -> bool {
-    (unsafe { hdfsHFlush(self.fs.raw, self.file) }) == 0
+  pub fn hflush(&self) -> Result<bool, HdfsErr> {
+    if unsafe { hdfsHFlush(self.fs.raw(), self.file) } == 0 {
+      Ok(true)
+    } else {
+      Err(self.fs.last_error("hflush", &self.path))
+    }
   }
 
   /// Similar to posix fsync, Flush out the data in client's
   /// user buffer. all the way to the disk device (but the disk may have
   /// it in its cache).
-  pub fn hsync(&self) -> bool {
-    (unsafe { hdfsHSync(self.fs.raw, self.file) }) == 0
+  pub fn hsync(&self) -> Result<bool, HdfsErr> {
+    if unsafe { hdfsHSync(self.fs.raw(), self.file) } == 0 {
+      Ok(true)
+    } else {
+      Err(self.fs.last_error("hsync", &self.path))
+    }
   }
 
   /// Determine if a file is open for read.
@@ -609,52 +1373,84 @@ impl<'a> HdfsFile<'a> {
   }
 
   /// Return a file path
-  pub fn path(&'a self) -> &'a str {
+  pub fn path(&self) -> &str {
     &self.path
   }
 
+  /// Get the HdfsFs which the file belongs to.
+  pub fn fs(&self) -> &HdfsFs {
+    &self.fs
+  }
+
+  /// Get a raw pointer of JNI API's hdfsFile
+  #[inline]
+  pub fn raw(&self) -> *const hdfsFile {
+    self.file
+  }
+
   /// Get the current offset in the file, in bytes.
   pub fn pos(&self) -> Result<u64, HdfsErr> {
-    let pos = unsafe {hdfsTell(self.fs.raw, self.file)};
+    let pos = unsafe {hdfsTell(self.fs.raw(), self.file)};
 
-    if pos > 0 {
+    if pos >= 0 {
       Ok(pos as u64)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.fs.last_error("pos", &self.path))
     }
   }
 
-  /// Read data from an open file.
-  pub fn read(&self, buf: &mut [u8]) -> Result<i32, HdfsErr> {
+  /// Positional read of data from an open file.
+  /// It returns ```Ok(0)``` if ```pos``` is at or beyond the end of file.
+  pub fn read_with_pos(&self, pos: i64, buf: &mut [u8]) -> Result<i32, HdfsErr> {
     let read_len = unsafe {
-      hdfsRead(self.fs.raw, self.file, buf.as_ptr() as *mut c_void,
-        buf.len() as tSize)
+      hdfsPread(self.fs.raw(), self.file, pos as tOffset,
+        buf.as_mut_ptr() as *mut c_void, io_len(buf.len()))
     };
 
-    if read_len > 0 {
+    if read_len >= 0 {
       Ok(read_len as i32)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.fs.last_error("read_with_pos", &self.path))
     }
   }
 
-  /// Positional read of data from an open file.
-  pub fn read_with_pos(&self, pos: i64, buf: &mut [u8]) -> Result<i32, HdfsErr> {
-    let read_len = unsafe {
-      hdfsPread(self.fs.raw, self.file, pos as tOffset,
-        buf.as_ptr() as *mut c_void, buf.len() as tSize)
-    };
+  /// Read multiple ranges given as ```(offset, length)``` pairs. Nearby ranges are
+  /// merged into a single positional read, and merged reads may be performed
+  /// in parallel (see ```ReadRangesOptions```).
+  ///
+  /// Every range is filled completely, or it fails with ```UnexpectedEof```.
+  pub fn read_ranges(&self, ranges: &[(u64, u64)], opts: &ReadRangesOptions)
+      -> Result<RangeBuffers, HdfsErr> {
 
-    if read_len > 0 {
-      Ok(read_len as i32)
-    } else {
-      Err(HdfsErr::Unknown)
+    ranges::read_ranges(self, ranges, opts)
+  }
+
+  /// Get read statistics of this file. It works only for files opened for read
+  /// in HDFS, and fails with ```ENOTSUP``` for other filesystems.
+  pub fn read_statistics(&self) -> Result<ReadStatistics, HdfsErr> {
+    let mut stats: *mut hdfsReadStatistics = ptr::null_mut();
+
+    if unsafe { hdfsFileGetReadStatistics(self.file, &mut stats) } != 0 {
+      return Err(self.fs.last_error("read_statistics", &self.path));
     }
+
+    let res = unsafe {
+      ReadStatistics {
+        total_bytes_read: (*stats).totalBytesRead,
+        total_local_bytes_read: (*stats).totalLocalBytesRead,
+        total_short_circuit_bytes_read: (*stats).totalShortCircuitBytesRead,
+        total_zero_copy_bytes_read: (*stats).totalZeroCopyBytesRead,
+        remote_bytes_read: hdfsReadStatisticsGetRemoteBytesRead(stats) as u64
+      }
+    };
+    unsafe { hdfsFileFreeReadStatistics(stats) };
+
+    Ok(res)
   }
 
   /// Perform a byte buffer read. If possible, this will be a zero-copy
   /// (mmap) read.
-  pub fn read_zc(&'a self, opts: &RzOptions, max_len: i32) -> Result<RzBuffer<'a>, HdfsErr> {
+  pub fn read_zc(&self, opts: &RzOptions, max_len: i32) -> Result<RzBuffer, HdfsErr> {
     let buf : *const hadoopRzBuffer = unsafe {
       hadoopReadZero(self.file, opts.ptr, max_len as int32_t)
     };
@@ -662,33 +1458,535 @@ impl<'a> HdfsFile<'a> {
     if !buf.is_null() {
       Ok(RzBuffer {file: self, ptr: buf})
     } else {
-      Err(HdfsErr::Unknown)
+      Err(self.fs.last_error("read_zc", &self.path))
     }
   }
+}
+
+impl Drop for HdfsFile {
+  fn drop(&mut self) {
+    if self.file.is_null() {
+      return;
+    }
 
-  /// Seek to given offset in file.
-  pub fn seek(&self, offset: u64) -> bool {
-    (unsafe { hdfsSeek(self.fs.raw, self.file, offset as tOffset) }) == 0
+    if unsafe {hdfsCloseFile(self.fs.raw(), self.file)} != 0 {
+      error!("Failed to close {}: {}", self.path, io::Error::last_os_error());
+    }
   }
+}
+
+/// Clamp a buffer length to the largest length which libhdfs can take at once.
+#[inline]
+fn io_len(len: usize) -> tSize {
+  cmp::min(len, i32::MAX as usize) as tSize
+}
+
+impl Read for HdfsFile {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read_len = unsafe {
+      hdfsRead(self.fs.raw(), self.file, buf.as_mut_ptr() as *mut c_void,
+        io_len(buf.len()))
+    };
+
+    if read_len >= 0 {
+      Ok(read_len as usize)
+    } else {
+      Err(io::Error::from(HdfsErr::last_os_error("read", &self.path)))
+    }
+  }
+}
+
+impl Write for HdfsFile {
+  /// It may write fewer bytes than ```buf.len()```. Use ```write_all```
+  /// in order to write the whole buffer.
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if buf.is_empty() {
+      return Ok(0);
+    }
 
-  /// Write data into an open file.
-  pub fn write(&self, buf: &[u8]) -> Result<i32, HdfsErr> {
     let written_len = unsafe {
-      hdfsWrite(self.fs.raw, self.file,
-        buf.as_ptr() as *mut c_void, buf.len() as tSize)
+      hdfsWrite(self.fs.raw(), self.file, buf.as_ptr() as *const c_void,
+        io_len(buf.len()))
     };
 
-    if written_len > 0 {
-      Ok(written_len)
+    if written_len >= 0 {
+      Ok(written_len as usize)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(io::Error::from(HdfsErr::last_os_error("write", &self.path)))
+    }
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    if unsafe { hdfsFlush(self.fs.raw(), self.file) } == 0 {
+      Ok(())
+    } else {
+      Err(io::Error::from(HdfsErr::last_os_error("flush", &self.path)))
+    }
+  }
+}
+
+impl Seek for HdfsFile {
+  /// Seek to an offset in bytes. Like ```hdfsSeek```, it works only for files
+  /// opened for read, except ```SeekFrom::Current(0)``` which just returns
+  /// the current offset.
+  fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+    let target = match pos {
+      SeekFrom::Start(offset) => {
+        if offset > i64::MAX as u64 { None } else { Some(offset as i64) }
+      },
+      SeekFrom::Current(delta) => {
+        let cur = unsafe { hdfsTell(self.fs.raw(), self.file) };
+        if cur < 0 {
+          return Err(io::Error::from(HdfsErr::last_os_error("seek", &self.path)));
+        }
+        if delta == 0 {
+          return Ok(cur as u64);
+        }
+        cur.checked_add(delta)
+      },
+      SeekFrom::End(delta) => {
+        let len = try!(self.fs.get_file_status(&self.path)).len() as u64;
+        if len > i64::MAX as u64 { None } else { (len as i64).checked_add(delta) }
+      }
+    };
+
+    let target = match target {
+      Some(target) if target >= 0 => target,
+      _ => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+        "invalid seek to a negative or overflowing position"))
+    };
+
+    if unsafe { hdfsSeek(self.fs.raw(), self.file, target as tOffset) } == 0 {
+      Ok(target as u64)
+    } else {
+      Err(io::Error::from(HdfsErr::last_os_error("seek", &self.path)))
+    }
+  }
+}
+
+/// A file written to a hidden temporary path in the same directory, which is
+/// renamed to the target path on ```commit()```. Readers never see a partially
+/// written file at the target path. Created by ```HdfsFs::create_atomic```.
+///
+/// If it is dropped without being committed, the temporary file is deleted.
+///
+/// HDFS cannot rename over an existing file, so overwriting renames the existing
+/// file aside, renames the new file to the target path, and then deletes the old
+/// one. Readers may find no file for a moment, but never a partial one.
+///
+/// ```ignore
+/// let mut writer = fs.create_atomic("/data/_SUCCESS", true).ok().unwrap();
+/// writer.write_all(b"done").unwrap();
+/// writer.commit().ok().unwrap();
+/// ```
+pub struct AtomicWriter {
+  /// None once committed or aborted
+  file: Option<HdfsFile>,
+  path: String,
+  tmp_path: String,
+  overwrite: bool
+}
+
+impl AtomicWriter {
+  /// Return the target path
+  pub fn path(&self) -> &str {
+    &self.path
+  }
+
+  /// Return the temporary path being written
+  pub fn temp_path(&self) -> &str {
+    &self.tmp_path
+  }
+
+  /// Sync and close the temporary file, and rename it to the target path.
+  /// Without overwrite, it fails with ```FileAlreadyExists``` if the target exists.
+  ///
+  /// On failure, the temporary file is deleted and an existing target is left as
+  /// it was. Only if the replaced file cannot be restored either, both the
+  /// temporary file and the replaced file are kept, and their paths are logged.
+  pub fn commit(mut self) -> Result<bool, HdfsErr> {
+    let file = self.file.take().unwrap();
+    let fs = file.fs().clone();
+
+    let res = match file.hsync() {
+      Ok(_) => file.close(),
+      Err(e) => {
+        drop(file);
+        Err(e)
+      }
+    };
+    if let Err(e) = res {
+      let _ = fs.delete(&self.tmp_path, false);
+      return Err(e);
+    }
+
+    let mut backup = None;
+    if self.overwrite {
+      let res = match fs.get_file_status(&self.path) {
+        Ok(ref status) if status.is_directory() =>
+          Err(HdfsErr::FileAlreadyExists(self.path.clone())),
+        Ok(_) => {
+          let backup_path = temp_path(&self.path);
+          let res = fs.rename_exclusive(&self.path, &backup_path);
+          backup = Some(backup_path);
+          res
+        },
+        Err(ref e) if e.is_not_found() => Ok(true),
+        Err(e) => Err(e)
+      };
+
+      if let Err(e) = res {
+        let _ = fs.delete(&self.tmp_path, false);
+        return Err(e);
+      }
+    }
+
+    match fs.rename_exclusive(&self.tmp_path, &self.path) {
+      Ok(_) => {
+        if let Some(backup_path) = backup {
+          if let Err(e) = fs.delete(&backup_path, false) {
+            warn!("Failed to delete the replaced file {}: {}", backup_path, e);
+          }
+        }
+        Ok(true)
+      },
+      Err(e) => {
+        if let Some(backup_path) = backup {
+          if let Err(restore_err) = fs.rename_exclusive(&backup_path, &self.path) {
+            error!("Failed to restore {} from {}: {}. The new data is kept in {}",
+              self.path, backup_path, restore_err, self.tmp_path);
+            return Err(e);
+          }
+        }
+        let _ = fs.delete(&self.tmp_path, false);
+        Err(e)
+      }
+    }
+  }
+
+  /// Discard the written data and delete the temporary file.
+  pub fn abort(mut self) -> Result<bool, HdfsErr> {
+    let file = self.file.take().unwrap();
+    let fs = file.fs().clone();
+    drop(file);
+    fs.delete(&self.tmp_path, false)
+  }
+}
+
+impl Write for AtomicWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.file.as_mut().unwrap().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Write::flush(self.file.as_mut().unwrap())
+  }
+}
+
+impl Drop for AtomicWriter {
+  fn drop(&mut self) {
+    if let Some(file) = self.file.take() {
+      let fs = file.fs().clone();
+      drop(file);
+      if let Err(e) = fs.delete(&self.tmp_path, false) {
+        error!("Failed to delete {}: {}", self.tmp_path, e);
+      }
+    }
+  }
+}
+
+/// Options of opening a file with ```HdfsFs::open_with_options```
+///
+/// HDFS files can be read, created or appended, but cannot be read and
+/// written at once, or written at arbitrary offsets. The options work as follows:
+///
+/// * ```read``` opens an existing file for read.
+/// * ```append``` opens an existing file for append. With ```create```, a missing
+///   file is created.
+/// * ```write``` with ```create``` creates a file. Unless ```overwrite``` is also
+///   set, it atomically fails with ```FileAlreadyExists``` if the file exists, like
+///   ```create_new```.
+/// * ```write``` with ```create_new``` creates a file, and atomically fails with
+///   ```FileAlreadyExists``` if the file exists.
+/// * ```write``` with only ```overwrite``` overwrites an existing file.
+///
+/// libhdfs always overwrites existing files on creation, so exclusive creation creates
+/// a temporary file, renames it, which HDFS refuses atomically if the target exists,
+/// and opens it for append. It needs more round trips to the NameNode, and the
+/// filesystem must support append (e.g., ```file:///``` does not). If the process
+/// dies in the middle, a hidden temporary file may be left in the same directory.
+///
+/// Block sizes of 2 GiB or more require the ```hadoop3``` feature.
+///
+/// ```ignore
+/// let file = fs.open_with_options("/data/part-0", OpenOptions::new()
+///   .set_write(true)
+///   .set_create_new(true)
+///   .set_replication(2)
+///   .set_block_size(512 * 1024 * 1024)).ok().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+  read: bool,
+  write: bool,
+  append: bool,
+  create: bool,
+  create_new: bool,
+  overwrite: bool,
+  buffer_size: i32,
+  replication: i16,
+  block_size: u64,
+  permission: Option<FsPermission>
+}
+
+impl OpenOptions {
+  /// All options are unset, and the configured buffer size, replication,
+  /// block size and permission are used.
+  pub fn new() -> OpenOptions {
+    OpenOptions {
+      read: false,
+      write: false,
+      append: false,
+      create: false,
+      create_new: false,
+      overwrite: false,
+      buffer_size: 0,
+      replication: 0,
+      block_size: 0,
+      permission: None
+    }
+  }
+
+  /// Set TRUE in order to open a file for read
+  pub fn set_read(&mut self, read: bool) -> &mut OpenOptions {
+    self.read = read;
+    self
+  }
+
+  /// Set TRUE in order to open a file for write
+  pub fn set_write(&mut self, write: bool) -> &mut OpenOptions {
+    self.write = write;
+    self
+  }
+
+  /// Set TRUE in order to open a file for append
+  pub fn set_append(&mut self, append: bool) -> &mut OpenOptions {
+    self.append = append;
+    self
+  }
+
+  /// Set TRUE in order to create a file if it does not exist
+  pub fn set_create(&mut self, create: bool) -> &mut OpenOptions {
+    self.create = create;
+    self
+  }
+
+  /// Set TRUE in order to create a file, failing if it exists
+  pub fn set_create_new(&mut self, create_new: bool) -> &mut OpenOptions {
+    self.create_new = create_new;
+    self
+  }
+
+  /// Set TRUE in order to replace an existing file
+  pub fn set_overwrite(&mut self, overwrite: bool) -> &mut OpenOptions {
+    self.overwrite = overwrite;
+    self
+  }
+
+  /// Set the buffer size for read or write. 0 means the configured value.
+  pub fn set_buffer_size(&mut self, buffer_size: i32) -> &mut OpenOptions {
+    self.buffer_size = buffer_size;
+    self
+  }
+
+  /// Set the replication factor of a new file. 0 means the configured value.
+  pub fn set_replication(&mut self, replication: i16) -> &mut OpenOptions {
+    self.replication = replication;
+    self
+  }
+
+  /// Set the block size of a new file. 0 means the configured value.
+  /// Block sizes of 2 GiB or more require the ```hadoop3``` feature, which
+  /// needs libhdfs of Hadoop 3.0 or later.
+  pub fn set_block_size(&mut self, block_size: u64) -> &mut OpenOptions {
+    self.block_size = block_size;
+    self
+  }
+
+  /// Set the permission of a new file, which is masked by the configured umask.
+  /// libhdfs cannot pass it on creation, so it is set right after creation.
+  pub fn set_permission(&mut self, perm: FsPermission) -> &mut OpenOptions {
+    self.permission = Some(perm);
+    self
+  }
+}
+
+/// Builder of ```HdfsFs```, wrapping ```hdfsBuilder``` of libhdfs.
+///
+/// Please use HdfsFsCache rather than connecting with it directly, unless you need
+/// a specific user, a Kerberos ticket cache or per-connection configurations.
+///
+/// ```ignore
+/// let mut builder = HdfsFsBuilder::new().ok().unwrap();
+/// builder
+///   .set_namenode("hdfs://localhost:8020")
+///   .set_user_name("hadoop");
+/// builder.set_conf("dfs.client.read.shortcircuit", "true").ok().unwrap();
+/// let fs = builder.connect().ok().unwrap();
+/// ```
+pub struct HdfsFsBuilder {
+  raw: *mut hdfsBuilder,
+  namenode: Option<String>,
+  port: Option<u16>,
+  /// libhdfs keeps only pointers of given strings until connecting
+  strings: Vec<CString>,
+  /// the first invalid argument, reported by ```connect```
+  error: Option<HdfsErr>
+}
+
+impl Drop for HdfsFsBuilder {
+  fn drop(&mut self) {
+    // hdfsBuilderConnect already freed the builder if it is null.
+    if !self.raw.is_null() {
+      unsafe { hdfsFreeBuilder(self.raw) };
     }
   }
 }
 
+impl HdfsFsBuilder {
+  pub fn new() -> Result<HdfsFsBuilder, HdfsErr> {
+    let raw = unsafe { hdfsNewBuilder() };
+    if raw.is_null() {
+      return Err(HdfsErr::last_os_error("new_builder", ""));
+    }
+
+    Ok(HdfsFsBuilder {
+      raw: raw,
+      namenode: None,
+      port: None,
+      strings: Vec::new(),
+      error: None
+    })
+  }
+
+  /// Keep a C string alive as long as the builder, and return its pointer.
+  /// A string with a NUL byte is replaced by an empty one, and makes ```connect``` fail.
+  fn keep(&mut self, s: &str) -> *const c_char {
+    let c_str = match CString::new(s) {
+      Ok(c_str) => c_str,
+      Err(_) => {
+        if self.error.is_none() {
+          self.error = Some(HdfsErr::InvalidArgument(
+            format!("string with a NUL byte: {:?}", s)));
+        }
+        CString::new("").unwrap()
+      }
+    };
+    let ptr = c_str.as_ptr();
+    self.strings.push(c_str);
+    ptr
+  }
+
+  /// Set the NameNode to connect to (e.g., ```hdfs://host:port```, ```file:///```
+  /// or ```default``` for the NameNode in the XML configuration files).
+  pub fn set_namenode(&mut self, namenode: &str) -> &mut HdfsFsBuilder {
+    let ptr = self.keep(namenode);
+    unsafe { hdfsBuilderSetNameNode(self.raw, ptr) };
+    self.namenode = Some(namenode.to_owned());
+    self
+  }
+
+  /// Set the port of the NameNode. It must not be given if the port is already
+  /// included in the NameNode given to ```set_namenode```.
+  pub fn set_namenode_port(&mut self, port: u16) -> &mut HdfsFsBuilder {
+    unsafe { hdfsBuilderSetNameNodePort(self.raw, port as tPort) };
+    self.port = Some(port);
+    self
+  }
+
+  /// Set the user name to use when connecting to the HDFS cluster.
+  pub fn set_user_name(&mut self, user_name: &str) -> &mut HdfsFsBuilder {
+    let ptr = self.keep(user_name);
+    unsafe { hdfsBuilderSetUserName(self.raw, ptr) };
+    self
+  }
+
+  /// Set the path to the Kerberos ticket cache to use when connecting.
+  pub fn set_kerb_ticket_cache_path(&mut self, path: &str) -> &mut HdfsFsBuilder {
+    let ptr = self.keep(path);
+    unsafe { hdfsBuilderSetKerbTicketCachePath(self.raw, ptr) };
+    self
+  }
+
+  /// Override a Hadoop configuration (e.g., ```dfs.client.read.shortcircuit```)
+  /// for this connection.
+  pub fn set_conf(&mut self, key: &str, value: &str)
+      -> Result<&mut HdfsFsBuilder, HdfsErr> {
+    let c_key = try!(CString::new(key).map_err(|_|
+      HdfsErr::InvalidArgument(format!("configuration key with a NUL byte: {:?}", key))));
+    let c_value = try!(CString::new(value).map_err(|_|
+      HdfsErr::InvalidArgument(format!("configuration value with a NUL byte: {:?}", value))));
+
+    if unsafe { hdfsBuilderConfSetStr(self.raw, c_key.as_ptr(), c_value.as_ptr()) } != 0 {
+      return Err(HdfsErr::last_os_error("set_conf", key));
+    }
+    self.strings.push(c_key);
+    self.strings.push(c_value);
+    Ok(self)
+  }
+
+  /// Connect to the NameNode. The native builder is freed whether or not
+  /// the connection succeeds.
+  ///
+  /// It always creates a new Java FileSystem instance, so closing the returned
+  /// HdfsFs, when its last clone is dropped, never closes a FileSystem shared
+  /// with other connections to the same NameNode.
+  pub fn connect(mut self) -> Result<HdfsFs, HdfsErr> {
+    if let Some(err) = self.error.take() {
+      return Err(err);
+    }
+
+    let url = effective_url(self.namenode.as_ref().map(|nn| &nn[..]), self.port);
+
+    info!("Connecting to Namenode ({})", &url);
+    unsafe { hdfsBuilderSetForceNewInstance(self.raw) };
+    let raw = unsafe { hdfsBuilderConnect(self.raw) };
+    self.raw = ptr::null_mut();
+
+    if raw.is_null() {
+      Err(HdfsErr::CannotConnectToNameNode(url))
+    } else {
+      Ok(HdfsFs::new(url, raw))
+    }
+  }
+}
 
 static LOCAL_FS_SCHEME: &'static str = "file";
 
+/// Get the URL which libhdfs connects to for a NameNode and a port given to
+/// ```HdfsFsBuilder```, in the same form as ```HdfsFsCache``` (e.g.,
+/// ```hdfs://localhost:8020``` or ```file:///```).
+fn effective_url(namenode: Option<&str>, port: Option<u16>) -> String {
+  let url = match namenode {
+    // libhdfs connects to the local filesystem without a NameNode
+    None => return "file:///".to_owned(),
+    Some("default") => HdfsConf::default_fs().unwrap_or_else(|| "file:///".to_owned()),
+    Some(namenode) => {
+      // like libhdfs, the default scheme is hdfs, and the port is appended
+      let scheme = if namenode.contains("://") { "" } else { "hdfs://" };
+      let suffix = port.map_or(String::new(), |port| format!(":{}", port));
+      format!("{}{}{}", scheme, namenode, suffix)
+    }
+  };
+
+  let path = HdfsPath::new(&url);
+  match (path.scheme(), path.authority()) {
+    (Some(scheme), _) if scheme.eq_ignore_ascii_case(LOCAL_FS_SCHEME) => "file:///".to_owned(),
+    (Some(scheme), Some(authority)) => format!("{}://{}", scheme, authority),
+    _ => url
+  }
+}
+
 /// for HDFS URL scheme (i.e., hdfs://)
 fn hdfs_scheme_handler(scheme: &str) -> SchemeType 
 {
@@ -704,29 +2002,49 @@ fn hdfs_scheme_handler(scheme: &str) -> SchemeType
 /// The original libhdfs allows only one HdfsFs instance for the same namenode. In otherwords,
 /// Some APIs of libhdfs are not thread-safe. So, You must get HdfsFs instance through HdfsFsCache, 
 /// caching initialized HdfsFs instances and returning them.  
-pub struct HdfsFsCache<'a> 
+///
+/// HdfsFsCache is ```Send``` and ```Sync```, and ```HdfsFsCache::global()``` gives
+/// a process-wide instance which all threads can share.
+///
+/// A connection is closed when it is evicted from or dropped with the cache and
+/// no HdfsFs handle of it is left. A cached connection which reports a fatal
+/// connection error is replaced by a new one on the next ```get```.
+pub struct HdfsFsCache
 {
-  fs_map: Mutex<HashMap<String, HdfsFs<'a>>>,
-  url_parser: UrlParser<'a>
+  fs_map: Mutex<HashMap<String, HdfsFs>>
 }
 
-impl<'a> HdfsFsCache<'a> 
+impl HdfsFsCache
 {
-  pub fn new() -> HdfsFsCache<'a> 
+  pub fn new() -> HdfsFsCache
   {
-    let mut url_parser = UrlParser::new();
-    url_parser.scheme_type_mapper(hdfs_scheme_handler);
-
     HdfsFsCache {
-      fs_map: Mutex::new(HashMap::new()),
-      url_parser: url_parser
+      fs_map: Mutex::new(HashMap::new())
+    }
+  }
+
+  /// Get the process-wide cache, which is created on the first call.
+  pub fn global() -> &'static HdfsFsCache
+  {
+    static INIT: Once = ONCE_INIT;
+    static mut CACHE: *const HdfsFsCache = 0 as *const HdfsFsCache;
+
+    unsafe {
+      INIT.call_once(|| {
+        // it lives until the process exits
+        CACHE = Box::into_raw(Box::new(HdfsFsCache::new()));
+      });
+      &*CACHE
     }
   }
 
   #[inline]
   fn get_namenode_uri(&self, path: &str) -> Result<String, HdfsErr> 
   {
-    match self.url_parser.parse(path) {
+    let mut url_parser = UrlParser::new();
+    url_parser.scheme_type_mapper(hdfs_scheme_handler);
+
+    match url_parser.parse(path) {
       Ok(url) => {
         
         if &url.scheme == LOCAL_FS_SCHEME {
@@ -752,43 +2070,77 @@ impl<'a> HdfsFsCache<'a>
     }
   }
 
-  pub fn get(&mut self, path: &str) -> Result<HdfsFs<'a>, HdfsErr> 
+  /// Get the HdfsFs of the namenode of ```path```, connecting to it if not cached.
+  pub fn get(&self, path: &str) -> Result<HdfsFs, HdfsErr> 
   {
     let namenode_uri = try!(self.get_namenode_uri(path));
- 
+
+    // Probing and connecting may take long, so they are done without the lock
+    // in order not to block other threads and namenodes.
+    let cached = self.fs_map.lock().unwrap().get(&namenode_uri).cloned();
+    let broken_raw = match cached {
+      Some(fs) => {
+        if fs.is_alive() {
+          return Ok(fs);
+        }
+        // reconnect if the cached connection is broken
+        warn!("Reconnecting to Namenode ({})", &namenode_uri);
+        Some(fs.raw())
+      },
+      None => None
+    };
+
+    let mut builder = try!(HdfsFsBuilder::new());
+    builder.set_namenode(&namenode_uri);
+    let hdfs_fs = try!(builder.connect());
+
     let mut map = self.fs_map.lock().unwrap();
-      
-    if !map.contains_key(&namenode_uri) {  
-      let hdfs_fs = unsafe {
-        let hdfs_builder = hdfsNewBuilder();
-        hdfsBuilderSetNameNode(hdfs_builder, str_to_chars(&namenode_uri));
-        info!("Connecting to Namenode ({})", &namenode_uri);
-        hdfsBuilderConnect(hdfs_builder)
-      };
-        
-      if hdfs_fs.is_null() {
-        return Err(HdfsErr::CannotConnectToNameNode(namenode_uri.clone()))
+    // another thread may have connected in the meantime
+    if let Some(fs) = map.get(&namenode_uri) {
+      if Some(fs.raw()) != broken_raw {
+        return Ok(fs.clone());
       }
-          
-      map.insert(
-        namenode_uri.clone(),
-        HdfsFs::new(namenode_uri.clone(), hdfs_fs));
     }
-      
-    Ok(map.get(&namenode_uri).unwrap().clone())
+
+    map.insert(namenode_uri, hdfs_fs.clone());
+    Ok(hdfs_fs)
+  }
+
+  /// Remove the HdfsFs of the namenode of ```path``` from the cache. The connection
+  /// is closed when all its handles, including the returned one, are dropped.
+  pub fn evict(&self, path: &str) -> Result<Option<HdfsFs>, HdfsErr>
+  {
+    let namenode_uri = try!(self.get_namenode_uri(path));
+    Ok(self.fs_map.lock().unwrap().remove(&namenode_uri))
+  }
+
+  /// Remove all HdfsFs from the cache. Each connection is closed when all its
+  /// handles are dropped.
+  pub fn disconnect_all(&self)
+  {
+    self.fs_map.lock().unwrap().clear();
   }
 }
 
 #[cfg(test)]
 mod test {
-  use std::rc::Rc;
-  use std::cell::RefCell;
+  use std::i64;
+  use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+  use std::sync::Arc;
+  use std::thread;
+  use std::time::{Duration, UNIX_EPOCH};
   
   use itertools::Itertools;
   
   use native::MiniDfsConf;
   use minidfs::*;
-  use super::HdfsFsCache;
+  use conf::HdfsConf;
+  use path::HdfsPath;
+  use perm::FsPermission;
+  use err::HdfsErr;
+  use ranges::ReadRangesOptions;
+  use std::os::unix::ffi::OsStrExt;
+  use super::{effective_url, lossy_string, FileStatus, HdfsFsBuilder, HdfsFsCache, OpenOptions};
   
   #[test]
   fn test_hdfs_connection() {
@@ -798,19 +2150,36 @@ mod test {
     let port = dfs.namenode_port().unwrap();
   
     let minidfs_addr = format!("hdfs://localhost:{}", port);
-    let cache = Rc::new(RefCell::new(HdfsFsCache::new()));
+    let cache = Arc::new(HdfsFsCache::new());
   
   
     // Parse namenode uris
-    assert_eq!("file:///".to_string(), cache.borrow_mut().get("file:/blah").ok().unwrap().url);
+    assert_eq!("file:///".to_string(), cache.get("file:/blah").ok().unwrap().url());
     let test_path = format!("hdfs://localhost:{}/users/test", port);
     println!("Trying to get {}", &test_path);
-    assert_eq!(minidfs_addr, cache.borrow_mut().get(&test_path).ok().unwrap().url);
+    assert_eq!(minidfs_addr, cache.get(&test_path).ok().unwrap().url());
+
+    // the cache and HdfsFs can be shared by threads, which get the same connection
+    let raw = cache.get(&test_path).ok().unwrap().raw() as usize;
+    let workers: Vec<_> = (0..4).map(|_| {
+      let cache = cache.clone();
+      let test_path = test_path.clone();
+      thread::spawn(move || {
+        let fs = cache.get(&test_path).ok().unwrap();
+        assert!(fs.exist("/"));
+        let global_fs = HdfsFsCache::global().get(&test_path).ok().unwrap();
+        (fs.raw() as usize, global_fs.raw() as usize)
+      })
+    }).collect();
+    let global_raw = HdfsFsCache::global().get(&test_path).ok().unwrap().raw() as usize;
+    for worker in workers {
+      assert_eq!((raw, global_raw), worker.join().unwrap());
+    }
   
   
   
     // create a file, check existence, and close
-    let fs = cache.borrow_mut().get(&test_path).ok().unwrap();
+    let fs = cache.get(&test_path).ok().unwrap();
     let test_file = "/test_file";
     let created_file = match fs.create(test_file) {
       Ok(f) => f,
@@ -835,6 +2204,7 @@ mod test {
     assert_eq!(&expected_path, file_info.name());
     assert!(!file_info.is_file());
     assert!(file_info.is_directory());
+    assert_eq!(0o755, file_info.permission().mode());
     
     
     let sub_dir_num = 3;
@@ -856,7 +2226,529 @@ mod test {
     for (expected, name) in izip!(expected_list, list.iter().map(|status| status.name())) {
       assert_eq!(expected, name);
     }
+
+    // FileStatus owns its fields, so it can be sent to other threads
+    let last = list.pop().unwrap();
+    let name = thread::spawn(move || last.name().to_owned()).join().unwrap();
+    assert_eq!(format!("hdfs://localhost:{}/dir1/2", port), name);
+
+    // connect as a specific user with a configuration override
+    let mut builder = HdfsFsBuilder::new().ok().unwrap();
+    builder
+      .set_namenode("localhost")
+      .set_namenode_port(port as u16)
+      .set_user_name("test_user")
+      .set_conf("dfs.client.read.shortcircuit", "false").ok().unwrap();
+    assert!(builder.set_conf("nul\0key", "value").is_err());
+    let user_fs = builder.connect().ok().unwrap();
+    assert_eq!(format!("hdfs://localhost:{}", port), user_fs.url());
+    assert_eq!(format!("hdfs://localhost:{}/dir1", port),
+      user_fs.qualify("/dir1").ok().unwrap().as_str());
+    assert!(user_fs.exist(test_file));
   
     dfs.stop();
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_errors() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    // failures carry errno and the failed path
+    let err = fs.get_file_status("/not_exist").err().unwrap();
+    assert!(err.is_not_found());
+    assert!(err.errno().is_some());
+    assert!(format!("{}", err).contains("/not_exist"));
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_working_dir() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    // relative paths are resolved against the working directory
+    fs.mkdir("/wd").ok().unwrap();
+    assert!(fs.set_working_dir("/wd").is_ok());
+    assert!(fs.working_dir().ok().unwrap().ends_with("/wd"));
+    let rel_file = fs.create("rel_file").ok().unwrap();
+    assert!(rel_file.path().ends_with("/wd/rel_file"));
+    assert!(rel_file.close().is_ok());
+    assert!(fs.exist("/wd/rel_file"));
+    assert_eq!(1, fs.list_status(".").ok().unwrap().len());
+    assert_eq!(fs.qualify("/wd/rel_file").ok().unwrap(), fs.qualify("rel_file").ok().unwrap());
+    assert!(fs.set_working_dir("/").is_ok());
+    assert!(!fs.exist("rel_file"));
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_set_times() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    let test_file = "/times_file";
+    fs.create(test_file).ok().unwrap().close().ok().unwrap();
+
+    // set times, which are kept in seconds
+    let mtime = UNIX_EPOCH + Duration::from_secs(1000000000);
+    let atime = UNIX_EPOCH + Duration::from_secs(1200000000);
+    assert!(fs.set_times(test_file, Some(mtime), Some(atime)).is_ok());
+    let status = fs.get_file_status(test_file).ok().unwrap();
+    assert_eq!(mtime, status.modified());
+    assert_eq!(atime, status.accessed());
+    assert!(fs.set_times(test_file, None, Some(mtime)).is_ok());
+    assert_eq!(mtime, fs.get_file_status(test_file).ok().unwrap().accessed());
+    assert_eq!(mtime, fs.get_file_status(test_file).ok().unwrap().modified());
+    assert!(fs.set_times("/not_exist", Some(mtime), None).is_err());
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_block_locations() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    let mut data_file = fs.create("/test_file_with_data").ok().unwrap();
+    data_file.write_all(b"0123456789").unwrap();
+    assert!(data_file.close().is_ok());
+
+    // block locations of a file smaller than a block
+    let locations = fs.block_locations("/test_file_with_data").ok().unwrap();
+    assert_eq!(1, locations.len());
+    assert_eq!(0, locations[0].offset());
+    assert_eq!(10, locations[0].len());
+    assert_eq!(1, locations[0].hosts().len());
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_content_summary() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    // content summary of the tree under /summary
+    fs.create("/summary/empty_file").ok().unwrap().close().ok().unwrap();
+    let mut sum_file = fs.create("/summary/sub/sum_file").ok().unwrap();
+    sum_file.write_all(b"01234").unwrap();
+    assert!(sum_file.close().is_ok());
+    let summary = fs.content_summary("/summary").ok().unwrap();
+    assert_eq!(5, summary.length);
+    assert_eq!(2, summary.file_count);
+    assert_eq!(2, summary.directory_count);
+    let replication = fs.get_file_status("/summary/sub/sum_file").ok().unwrap().replica_count();
+    assert_eq!(5 * replication as u64, summary.space_consumed);
+    assert_eq!(1, fs.content_summary("/summary/sub/sum_file").ok().unwrap().file_count);
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_permission() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    // permission of a new directory is masked by umask
+    let umask = HdfsConf::umask();
+    fs.mkdir_with_permission("/dir_perm", FsPermission::dir_default()).ok().unwrap();
+    let perm = fs.get_file_status("/dir_perm").ok().unwrap().permission();
+    assert_eq!(FsPermission::dir_default().apply_umask(umask), perm);
+    fs.chmod("/dir_perm", "1777".parse().ok().unwrap()).ok().unwrap();
+    let perm = fs.get_file_status("/dir_perm").ok().unwrap().permission();
+    assert!(perm.sticky_bit());
+    assert_eq!("rwxrwxrwt", perm.to_string());
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_qualified_paths() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    fs.mkdir("/dir1/0").ok().unwrap();
+
+    // qualified names can be converted from and to bare paths
+    let first = fs.get_file_status("/dir1/0").ok().unwrap().path();
+    assert_eq!(format!("hdfs://localhost:{}/dir1/0", port), first.as_str());
+    assert_eq!(Some(HdfsPath::new("/dir1/0")), first.unqualify(fs.url()));
+    assert_eq!(first, fs.qualify("/dir1/0").ok().unwrap());
+    assert_eq!(Some("0"), first.file_name());
+    assert_eq!(fs.qualify("/dir1").ok().unwrap(), first.parent().unwrap());
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_effective_url() {
+    assert_eq!("hdfs://nn:8020", effective_url(Some("nn"), Some(8020)));
+    assert_eq!("hdfs://nn", effective_url(Some("nn"), None));
+    assert_eq!("hdfs://nn:8020", effective_url(Some("hdfs://nn:8020/"), None));
+    assert_eq!("file:///", effective_url(Some("file:///"), None));
+    assert_eq!("file:///", effective_url(None, None));
+  }
+
+  #[test]
+  fn test_non_utf8_names() {
+    let (name, name_raw) = lossy_string(b"/dir/bad\xffname".to_vec());
+    let (owner, owner_raw) = lossy_string(b"owner".to_vec());
+    assert!(owner_raw.is_none());
+
+    let status = FileStatus {
+      name: name,
+      name_raw: name_raw,
+      is_directory: true,
+      len: 0,
+      block_size: 0,
+      replication: 0,
+      owner: owner,
+      owner_raw: owner_raw,
+      group: "group".to_owned(),
+      group_raw: None,
+      permission: FsPermission::from_mode(0o755),
+      modified: UNIX_EPOCH,
+      accessed: UNIX_EPOCH,
+      encrypted: false
+    };
+
+    assert_eq!("/dir/bad\u{fffd}name", status.name());
+    assert_eq!(b"/dir/bad\xffname", status.name_bytes());
+    assert!(status.try_name().is_err());
+    assert_eq!(Ok("owner"), status.try_owner());
+    assert_eq!(b"group", status.group_bytes());
+    assert_eq!(b"/dir/bad\xffname", status.name_os().as_bytes());
+  }
+
+  #[test]
+  fn test_odd_paths() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+    fs.mkdir("/odd").ok().unwrap();
+
+    let names = ["with space", "ünïcödé", "percent%20", "hash#1"];
+    for name in &names {
+      let path = format!("/odd/{}", name);
+      let mut file = fs.create(&path).ok().unwrap();
+      file.write_all(name.as_bytes()).unwrap();
+      assert!(file.close().is_ok());
+
+      assert!(fs.exist(&path));
+      assert_eq!(name.len(), fs.get_file_status(&path).ok().unwrap().len());
+      let mut content = String::new();
+      fs.open(&path).ok().unwrap().read_to_string(&mut content).unwrap();
+      assert_eq!(*name, content);
+    }
+
+    let mut listed: Vec<String> = fs.list_status("/odd").ok().unwrap().iter()
+      .map(|status| status.path().file_name().unwrap().to_owned()).collect();
+    listed.sort();
+    let mut expected: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+    expected.sort();
+    assert_eq!(expected, listed);
+
+    assert!(fs.rename("/odd/with space", "/odd/with  two spaces").is_ok());
+    assert!(fs.exist("/odd/with  two spaces"));
+    assert!(fs.delete("/odd/ünïcödé", false).is_ok());
+    assert!(!fs.exist("/odd/ünïcödé"));
+
+    // a NUL byte is rejected before reaching libhdfs
+    let nul_path = "/odd/nul\0byte";
+    assert!(!fs.exist(nul_path));
+    match fs.mkdir(nul_path) {
+      Err(HdfsErr::InvalidPath(ref path)) => assert_eq!(nul_path, &path[..]),
+      _ => panic!("a path with a NUL byte must be rejected")
+    }
+    assert!(fs.create(nul_path).is_err());
+    assert!(fs.open(nul_path).is_err());
+    assert!(fs.rename("/odd/hash#1", nul_path).is_err());
+    assert!(fs.chown("/odd/hash#1", "nul\0owner", "group").is_err());
+    assert!(fs.exist("/odd/hash#1"));
+
+    let mut builder = HdfsFsBuilder::new().ok().unwrap();
+    builder.set_namenode(&format!("hdfs://localhost:{}", port)).set_user_name("nul\0user");
+    assert!(builder.connect().is_err());
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_cache_lifecycle() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+    let url = format!("hdfs://localhost:{}/", port);
+
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&url).ok().unwrap();
+    assert_eq!(fs.raw(), cache.get(&url).ok().unwrap().raw());
+
+    // an evicted connection is kept until all of its handles are dropped
+    let evicted = cache.evict(&url).ok().unwrap().unwrap();
+    assert!(cache.evict(&url).ok().unwrap().is_none());
+    assert!(!evicted.disconnect().ok().unwrap());
+    {
+      let mut file = fs.create("/lifecycle").ok().unwrap();
+      file.write_all(b"abc").unwrap();
+      assert!(file.close().is_ok());
+    }
+    assert!(fs.disconnect().ok().unwrap());
+
+    // a new connection is made after eviction
+    let fs = cache.get(&url).ok().unwrap();
+    assert!(fs.exist("/lifecycle"));
+    cache.disconnect_all();
+    assert!(fs.exist("/lifecycle"));
+    assert!(fs.disconnect().ok().unwrap());
+
+    // a routine failure keeps the cached connection
+    let fs = cache.get(&url).ok().unwrap();
+    assert!(fs.get_file_status("/not_exist").is_err());
+    assert_eq!(fs.raw(), cache.get(&url).ok().unwrap().raw());
+
+    // a connection which fails after the cluster stops is replaced
+    dfs.stop();
+    assert!(fs.get_file_status("/lifecycle").is_err());
+    let reconnected = cache.get(&url).ok().unwrap();
+    assert!(fs.raw() != reconnected.raw());
+  }
+
+  #[test]
+  fn test_io_traits() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    let test_file = "/test_io_traits";
+    let mut writer = fs.create(test_file).ok().unwrap();
+    writer.write_all(b"line1\nline2\n").unwrap();
+    assert_eq!(12, writer.seek(SeekFrom::Current(0)).unwrap());
+    assert!(writer.close().is_ok());
+
+    // read at EOF returns 0 rather than an error
+    let mut reader = fs.open(test_file).ok().unwrap();
+    assert_eq!(0, reader.pos().ok().unwrap());
+    let mut content = String::new();
+    reader.read_to_string(&mut content).unwrap();
+    assert_eq!("line1\nline2\n", content);
+    let stats = reader.read_statistics().ok().unwrap();
+    assert_eq!(12, stats.total_bytes_read);
+    assert_eq!(stats.total_bytes_read,
+      stats.total_local_bytes_read + stats.remote_bytes_read);
+    assert_eq!(0, reader.read(&mut [0u8; 4]).unwrap());
+
+    assert_eq!(6, reader.seek(SeekFrom::Start(6)).unwrap());
+    assert_eq!(8, reader.seek(SeekFrom::Current(2)).unwrap());
+    assert_eq!(10, reader.seek(SeekFrom::End(-2)).unwrap());
+    assert!(reader.seek(SeekFrom::End(-13)).is_err());
+    assert!(reader.seek(SeekFrom::Current(i64::MAX)).is_err());
+    assert!(reader.seek(SeekFrom::End(i64::MAX)).is_err());
+    assert!(reader.seek(SeekFrom::Start(u64::max_value())).is_err());
+    assert_eq!(10, reader.seek(SeekFrom::Current(0)).unwrap());
+
+    reader.seek(SeekFrom::Start(0)).unwrap();
+    let lines: Vec<String> =
+      BufReader::new(&mut reader).lines().map(|l| l.unwrap()).collect();
+    assert_eq!(vec!["line1".to_string(), "line2".to_string()], lines);
+    assert!(reader.close().is_ok());
+
+    // a file dropped without close() is closed, so it can be opened for append
+    {
+      let mut writer = fs.create_with_overwrite(test_file, true).ok().unwrap();
+      writer.write_all(b"dropped").unwrap();
+    }
+    let appender = fs.append(test_file).ok().unwrap();
+    assert!(appender.close().is_ok());
+    assert_eq!(7, fs.get_file_status(test_file).ok().unwrap().len());
+
+    // read ranges, merged or not, sequentially and in parallel
+    let ranges_file = "/test_read_ranges";
+    let mut writer = fs.create(ranges_file).ok().unwrap();
+    writer.write_all(b"0123456789abcdefghij").unwrap();
+    assert!(writer.close().is_ok());
+
+    let reader = fs.open(ranges_file).ok().unwrap();
+    let ranges = [(10, 3), (0, 2), (3, 2), (18, 2), (5, 0)];
+    let mut opts = ReadRangesOptions::new();
+    for &(max_gap, parallelism) in &[(0, 1), (2, 1), (0, 3)] {
+      opts.set_max_gap(max_gap).set_parallelism(parallelism);
+      let buffers = reader.read_ranges(&ranges, &opts).ok().unwrap();
+      assert_eq!(5, buffers.len());
+      assert_eq!(b"abc", &buffers[0]);
+      assert_eq!(b"01", &buffers[1]);
+      assert_eq!(b"34", &buffers[2]);
+      assert_eq!(b"ij", &buffers[3]);
+      assert!(buffers[4].is_empty());
+    }
+    match reader.read_ranges(&[(0, 2), (18, 3)], &opts) {
+      Err(HdfsErr::UnexpectedEof { offset, .. }) => assert_eq!(20, offset),
+      _ => panic!("reading beyond the end of file must fail")
+    }
+    assert!(reader.close().is_ok());
+
+    // a file outlives the HdfsFs it is opened from, and can be moved to other threads
+    let open_file = |path: &str| {
+      let fs = HdfsFsCache::new().get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+      fs.open(path).ok().unwrap()
+    };
+    let mut reader = open_file(test_file);
+    let content = thread::spawn(move || {
+      let mut content = String::new();
+      reader.read_to_string(&mut content).unwrap();
+      content
+    }).join().unwrap();
+    assert_eq!("dropped", content);
+
+    // open options
+    match fs.open_with_options(test_file, OpenOptions::new().set_write(true).set_create_new(true)) {
+      Err(HdfsErr::FileAlreadyExists(_)) => {},
+      _ => panic!("creating an existing file exclusively must fail")
+    }
+    assert!(fs.create(test_file).is_err());
+    assert_eq!(7, fs.get_file_status(test_file).ok().unwrap().len());
+
+    let appended_file = "/test_open_options";
+    let mut appender = fs.open_with_options(appended_file,
+      OpenOptions::new().set_append(true).set_create(true)).ok().unwrap();
+    appender.write_all(b"abc").unwrap();
+    assert!(appender.close().is_ok());
+    assert_eq!(3, fs.get_file_status(appended_file).ok().unwrap().len());
+
+    match fs.open_with_options(appended_file, OpenOptions::new().set_read(true).set_write(true)) {
+      Err(HdfsErr::InvalidArgument(_)) => {},
+      _ => panic!("reading and writing at once must fail")
+    }
+    assert!(fs.open_with_options("/not_exist", OpenOptions::new().set_write(true)
+      .set_overwrite(true)).err().unwrap().is_not_found());
+
+    // atomic writes appear only on commit, and leave no temporary files behind
+    fs.mkdir("/atomic").ok().unwrap();
+    let target = "/atomic/out";
+    let mut writer = fs.create_atomic(target, false).ok().unwrap();
+    writer.write_all(b"first").unwrap();
+    assert!(!fs.exist(target));
+    assert!(fs.exist(writer.temp_path()));
+    assert!(writer.commit().is_ok());
+    assert_eq!(5, fs.get_file_status(target).ok().unwrap().len());
+
+    let mut writer = fs.create_atomic(target, false).ok().unwrap();
+    writer.write_all(b"second").unwrap();
+    match writer.commit() {
+      Err(HdfsErr::FileAlreadyExists(_)) => {},
+      _ => panic!("committing over an existing file without overwrite must fail")
+    }
+
+    {
+      let mut writer = fs.create_atomic(target, true).ok().unwrap();
+      writer.write_all(b"dropped").unwrap();
+    }
+    assert_eq!(5, fs.get_file_status(target).ok().unwrap().len());
+
+    let mut writer = fs.create_atomic(target, true).ok().unwrap();
+    writer.write_all(b"second").unwrap();
+    assert!(writer.commit().is_ok());
+    assert_eq!(6, fs.get_file_status(target).ok().unwrap().len());
+    assert_eq!(1, fs.list_status("/atomic").ok().unwrap().len());
+
+    dfs.stop();
+  }
+
+  #[test]
+  fn test_large_block_size() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    let test_file = "/test_large_block_size";
+    let big_block = 4 * 1024 * 1024 * 1024;
+    let res = fs.open_with_options(test_file, OpenOptions::new()
+      .set_write(true)
+      .set_create(true)
+      .set_block_size(big_block));
+
+    if cfg!(feature = "hadoop3") {
+      assert!(res.ok().unwrap().close().is_ok());
+      let status = fs.get_file_status(test_file).ok().unwrap();
+      assert_eq!(0, status.len());
+      assert_eq!(big_block, status.block_size() as u64);
+    } else {
+      match res {
+        Err(HdfsErr::InvalidArgument(_)) => assert!(!fs.exist(test_file)),
+        _ => panic!("block sizes over 2 GiB require the hadoop3 feature")
+      }
+    }
+
+    dfs.stop();
+  }
+
+  #[test]
+  #[cfg(feature = "hadoop2_7")]
+  fn test_truncate() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    // roll back a tail, and append after the recovery of the last block
+    let log_file = "/test_truncate";
+    let mut writer = fs.create(log_file).ok().unwrap();
+    writer.write_all(b"0123456789").unwrap();
+    assert!(writer.close().is_ok());
+
+    assert!(fs.truncate(log_file, 20).is_err());
+    if !fs.truncate(log_file, 4).ok().unwrap() {
+      assert!(fs.wait_for_truncate(log_file, Duration::from_secs(60)).ok().unwrap());
+    }
+    assert_eq!(4, fs.get_file_status(log_file).ok().unwrap().len());
+
+    let mut appender = fs.append(log_file).ok().unwrap();
+    appender.write_all(b"ab").unwrap();
+    assert!(appender.close().is_ok());
+    let mut content = String::new();
+    fs.open(log_file).ok().unwrap().read_to_string(&mut content).unwrap();
+    assert_eq!("0123ab", content);
+
+    // truncating at a block boundary completes at once
+    assert!(fs.truncate(log_file, 0).ok().unwrap());
+    assert!(fs.wait_for_truncate(log_file, Duration::from_secs(0)).ok().unwrap());
+    assert!(fs.truncate("/not_exist", 0).err().unwrap().is_not_found());
+
+    dfs.stop();
+  }
+}
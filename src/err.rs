@@ -1,4 +1,14 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use libc::{c_int, EACCES, ECONNABORTED, ECONNREFUSED, ECONNRESET, EDQUOT, EEXIST,
+  EHOSTUNREACH, ENETUNREACH, ENOENT, ENOTCONN, ENOTEMPTY, EPIPE, ESHUTDOWN, ETIMEDOUT};
+
+use native::EINTERNAL;
+
 /// Errors which can occur during accessing Hdfs cluster 
+#[derive(Debug)]
 pub enum HdfsErr {
   Unknown,
   /// file path
@@ -8,5 +18,170 @@ pub enum HdfsErr {
   /// namenode address      
   CannotConnectToNameNode(String),
   /// URL 
-  InvalidUrl(String) 
-}
\ No newline at end of file
+  InvalidUrl(String),
+  /// path which libhdfs cannot take (e.g., with a NUL byte)
+  InvalidPath(String),
+  /// reason
+  InvalidArgument(String),
+  /// The file ended before all requested bytes were read.
+  UnexpectedEof {
+    /// file path
+    path: String,
+    /// offset where the file ended
+    offset: u64
+  },
+  /// A libhdfs call failed and set ```errno```.
+  Native {
+    /// operation which failed (e.g., ```delete```)
+    op: &'static str,
+    /// path on which the operation failed, or an empty string
+    path: String,
+    /// errno set by libhdfs. ```EINTERNAL``` means an unclassified Java exception.
+    errno: i32
+  }
+}
+
+impl HdfsErr {
+  /// Build an error from ```errno``` set by the libhdfs call which just failed.
+  /// It must be called right after the failed call, before errno is overwritten.
+  pub fn last_os_error(op: &'static str, path: &str) -> HdfsErr {
+    let errno = match io::Error::last_os_error().raw_os_error() {
+      Some(0) | None => EINTERNAL,
+      Some(errno) => errno
+    };
+
+    HdfsErr::Native {
+      op: op,
+      path: path.to_owned(),
+      errno: errno
+    }
+  }
+
+  /// Get errno if this error comes from a failed libhdfs call.
+  pub fn errno(&self) -> Option<i32> {
+    match *self {
+      HdfsErr::Native { errno, .. } => Some(errno),
+      _ => None
+    }
+  }
+
+  /// Get the ```std::io::ErrorKind``` which best describes this error.
+  pub fn kind(&self) -> io::ErrorKind {
+    match *self {
+      HdfsErr::Unknown => io::ErrorKind::Other,
+      HdfsErr::FileNotFound(_) => io::ErrorKind::NotFound,
+      HdfsErr::FileAlreadyExists(_) => io::ErrorKind::AlreadyExists,
+      HdfsErr::CannotConnectToNameNode(_) => io::ErrorKind::ConnectionRefused,
+      HdfsErr::InvalidUrl(_) => io::ErrorKind::InvalidInput,
+      HdfsErr::InvalidPath(_) => io::ErrorKind::InvalidInput,
+      HdfsErr::InvalidArgument(_) => io::ErrorKind::InvalidInput,
+      HdfsErr::UnexpectedEof { .. } => io::ErrorKind::UnexpectedEof,
+      HdfsErr::Native { errno, .. } if errno == EINTERNAL => io::ErrorKind::Other,
+      HdfsErr::Native { errno, .. } => io::Error::from_raw_os_error(errno).kind()
+    }
+  }
+
+  #[inline]
+  fn has_errno(&self, expected: c_int) -> bool {
+    self.errno() == Some(expected)
+  }
+
+  /// Does the file or directory not exist?
+  pub fn is_not_found(&self) -> bool {
+    match *self {
+      HdfsErr::FileNotFound(_) => true,
+      _ => self.has_errno(ENOENT)
+    }
+  }
+
+  /// Does the file or directory already exist?
+  pub fn is_already_exists(&self) -> bool {
+    match *self {
+      HdfsErr::FileAlreadyExists(_) => true,
+      _ => self.has_errno(EEXIST)
+    }
+  }
+
+  /// Is the operation denied by HDFS permission checking?
+  pub fn is_permission_denied(&self) -> bool {
+    self.has_errno(EACCES)
+  }
+
+  /// Is a namespace or diskspace quota exceeded?
+  pub fn is_quota_exceeded(&self) -> bool {
+    self.has_errno(EDQUOT)
+  }
+
+  /// Is the directory to be deleted not empty?
+  pub fn is_directory_not_empty(&self) -> bool {
+    self.has_errno(ENOTEMPTY)
+  }
+
+  /// Is the connection to the cluster lost or not established?
+  ///
+  /// libhdfs reports most network failures as ```EINTERNAL```, so ```false```
+  /// does not guarantee that the connection is alive.
+  pub fn is_connection_error(&self) -> bool {
+    match *self {
+      HdfsErr::CannotConnectToNameNode(_) => true,
+      HdfsErr::Native { errno, .. } => match errno {
+        ECONNABORTED | ECONNREFUSED | ECONNRESET | EHOSTUNREACH | ENETUNREACH |
+        ENOTCONN | EPIPE | ESHUTDOWN | ETIMEDOUT => true,
+        _ => false
+      },
+      _ => false
+    }
+  }
+}
+
+impl fmt::Display for HdfsErr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      HdfsErr::Unknown => write!(f, "unknown hdfs error"),
+      HdfsErr::FileNotFound(ref path) => write!(f, "file not found: {}", path),
+      HdfsErr::FileAlreadyExists(ref path) =>
+        write!(f, "file already exists: {}", path),
+      HdfsErr::CannotConnectToNameNode(ref addr) =>
+        write!(f, "cannot connect to namenode: {}", addr),
+      HdfsErr::InvalidUrl(ref url) => write!(f, "invalid url: {}", url),
+      HdfsErr::InvalidPath(ref path) => write!(f, "invalid path: {:?}", path),
+      HdfsErr::InvalidArgument(ref reason) =>
+        write!(f, "invalid argument: {}", reason),
+      HdfsErr::UnexpectedEof { ref path, offset } =>
+        write!(f, "unexpected end of file at {} of {}", offset, path),
+      HdfsErr::Native { op, ref path, errno } => {
+        try!(write!(f, "{} failed", op));
+        if !path.is_empty() {
+          try!(write!(f, " on {}", path));
+        }
+        if errno == EINTERNAL {
+          write!(f, ": internal error (see the Java exception logged by libhdfs)")
+        } else {
+          write!(f, ": {}", io::Error::from_raw_os_error(errno))
+        }
+      }
+    }
+  }
+}
+
+impl Error for HdfsErr {
+  fn description(&self) -> &str {
+    match *self {
+      HdfsErr::Unknown => "unknown hdfs error",
+      HdfsErr::FileNotFound(_) => "file not found",
+      HdfsErr::FileAlreadyExists(_) => "file already exists",
+      HdfsErr::CannotConnectToNameNode(_) => "cannot connect to namenode",
+      HdfsErr::InvalidUrl(_) => "invalid url",
+      HdfsErr::InvalidPath(_) => "invalid path",
+      HdfsErr::InvalidArgument(_) => "invalid argument",
+      HdfsErr::UnexpectedEof { .. } => "unexpected end of file",
+      HdfsErr::Native { .. } => "libhdfs call failed"
+    }
+  }
+}
+
+impl From<HdfsErr> for io::Error {
+  fn from(err: HdfsErr) -> io::Error {
+    io::Error::new(err.kind(), err)
+  }
+}
@@ -0,0 +1,421 @@
+use dfs::{FileStatus, HdfsFs};
+use err::HdfsErr;
+
+/// Expand a glob pattern against a filesystem. Braces are expanded first,
+/// and then each expanded pattern is matched level by level with ```list_status```.
+pub fn glob(fs: &HdfsFs, pattern: &str) -> Result<Vec<FileStatus>, HdfsErr> {
+  let mut results = Vec::new();
+
+  for expanded in try!(expand_braces(pattern)) {
+    try!(glob_expanded(fs, &expanded, &mut results));
+  }
+
+  results.sort_by(|a, b| a.name_bytes().cmp(b.name_bytes()));
+  results.dedup_by(|a, b| a.name_bytes() == b.name_bytes());
+  Ok(results)
+}
+
+fn invalid_pattern(pattern: &str, reason: &str) -> HdfsErr {
+  HdfsErr::InvalidArgument(format!("{} in glob pattern '{}'", reason, pattern))
+}
+
+/// Expand a pattern without braces level by level. Paths are kept as bytes,
+/// so that entries whose names are not valid UTF-8 are not lost. Such names are
+/// matched with invalid sequences replaced by ```U+FFFD```.
+fn glob_expanded(fs: &HdfsFs, pattern: &str, results: &mut Vec<FileStatus>)
+    -> Result<(), HdfsErr> {
+
+  let (root, path) = try!(split_root(fs, pattern));
+  let mut components = Vec::new();
+  for component in path.split('/').filter(|c| !c.is_empty()) {
+    components.push(try!(Pattern::compile(component)));
+  }
+
+  // Paths matched so far, with their statuses if they are already known.
+  let mut candidates: Vec<(Vec<u8>, Option<FileStatus>)> = vec![(root.into_bytes(), None)];
+
+  for (idx, component) in components.iter().enumerate() {
+    let is_last = idx + 1 == components.len();
+    let mut matched = Vec::new();
+
+    for (parent, parent_status) in candidates {
+      if !component.has_wildcard {
+        matched.push((join(&parent, component.literal().as_bytes()), None));
+        continue;
+      }
+
+      // Listing a file would return the file itself.
+      let is_dir = match parent_status {
+        Some(status) => status.is_directory(),
+        None => match fs.get_file_status_bytes(&parent) {
+          Ok(status) => status.is_directory(),
+          Err(ref e) if e.is_not_found() => false,
+          Err(e) => return Err(e)
+        }
+      };
+      if !is_dir {
+        continue;
+      }
+
+      let children = match fs.list_status_bytes(&parent) {
+        Ok(children) => children,
+        Err(ref e) if e.is_not_found() => continue,
+        Err(e) => return Err(e)
+      };
+
+      for child in children {
+        if !is_last && !child.is_directory() {
+          continue;
+        }
+
+        let name = file_name(child.name_bytes()).to_vec();
+        if component.matches(&String::from_utf8_lossy(&name)) {
+          matched.push((join(&parent, &name), Some(child)));
+        }
+      }
+    }
+
+    candidates = matched;
+  }
+
+  for (path, status) in candidates {
+    match status {
+      Some(status) => results.push(status),
+      None => match fs.get_file_status_bytes(&path) {
+        Ok(status) => results.push(status),
+        Err(ref e) if e.is_not_found() => {},
+        Err(e) => return Err(e)
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Split a pattern into the root directory and the path relative to it.
+/// A relative pattern is resolved against the working directory.
+fn split_root(fs: &HdfsFs, pattern: &str) -> Result<(String, String), HdfsErr> {
+  if let Some(scheme_end) = pattern.find("://") {
+    let authority_start = scheme_end + 3;
+    match pattern[authority_start..].find('/') {
+      Some(slash) => {
+        let path_start = authority_start + slash + 1;
+        Ok((pattern[..path_start].to_owned(), pattern[path_start..].to_owned()))
+      },
+      None => Ok((format!("{}/", pattern), String::new()))
+    }
+  } else if pattern.starts_with("/") {
+    Ok(("/".to_owned(), pattern[1..].to_owned()))
+  } else {
+    Ok((try!(fs.working_dir()), pattern.to_owned()))
+  }
+}
+
+fn join(parent: &[u8], name: &[u8]) -> Vec<u8> {
+  let mut path = parent.to_vec();
+  if !parent.ends_with(b"/") {
+    path.push(b'/');
+  }
+  path.extend_from_slice(name);
+  path
+}
+
+fn file_name(path: &[u8]) -> &[u8] {
+  match path.iter().rposition(|&b| b == b'/') {
+    Some(idx) => &path[idx + 1..],
+    None => path
+  }
+}
+
+/// Expand all (possibly nested) ```{a,b}``` alternatives into separate patterns.
+fn expand_braces(pattern: &str) -> Result<Vec<String>, HdfsErr> {
+  let chars: Vec<char> = pattern.chars().collect();
+
+  // find the first top-level '{', its alternatives and its matching '}'
+  let mut open = None;
+  let mut commas = Vec::new();
+  let mut depth = 0;
+  let mut idx = 0;
+  while idx < chars.len() {
+    match chars[idx] {
+      '\\' => idx += 1,
+      '{' => {
+        if depth == 0 {
+          open = Some(idx);
+        }
+        depth += 1;
+      },
+      ',' if depth == 1 => commas.push(idx),
+      '}' if depth > 0 => {
+        depth -= 1;
+        if depth == 0 {
+          break;
+        }
+      },
+      _ => {}
+    }
+    idx += 1;
+  }
+
+  let open = match open {
+    Some(open) => open,
+    None => return Ok(vec![pattern.to_owned()])
+  };
+  if depth > 0 {
+    return Err(invalid_pattern(pattern, "unclosed '{'"));
+  }
+  let close = idx;
+
+  let prefix: String = chars[..open].iter().cloned().collect();
+  let suffix: String = chars[close + 1..].iter().cloned().collect();
+  let mut bounds = vec![open];
+  bounds.extend(commas);
+  bounds.push(close);
+
+  let mut expanded = Vec::new();
+  for pair in bounds.windows(2) {
+    let alternative: String = chars[pair[0] + 1..pair[1]].iter().cloned().collect();
+    let sub_pattern = format!("{}{}{}", prefix, alternative, suffix);
+    expanded.extend(try!(expand_braces(&sub_pattern)));
+  }
+
+  Ok(expanded)
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+  Char(char),
+  AnyChar,
+  AnyChars,
+  Class { negated: bool, ranges: Vec<(char, char)> }
+}
+
+impl Token {
+  fn matches(&self, c: char) -> bool {
+    match *self {
+      Token::Char(expected) => c == expected,
+      Token::AnyChar => true,
+      Token::AnyChars => true,
+      Token::Class { negated, ref ranges } =>
+        ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != negated
+    }
+  }
+}
+
+/// Glob pattern of a single path component, without braces
+struct Pattern {
+  tokens: Vec<Token>,
+  has_wildcard: bool
+}
+
+impl Pattern {
+  fn compile(glob: &str) -> Result<Pattern, HdfsErr> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut tokens = Vec::new();
+    let mut has_wildcard = false;
+    let mut idx = 0;
+
+    // get the character at idx, taking a backslash escape
+    let unescape = |idx: &mut usize| -> Result<char, HdfsErr> {
+      if chars[*idx] == '\\' {
+        *idx += 1;
+        if *idx == chars.len() {
+          return Err(invalid_pattern(glob, "dangling '\\'"));
+        }
+      }
+      Ok(chars[*idx])
+    };
+
+    while idx < chars.len() {
+      match chars[idx] {
+        '*' => {
+          has_wildcard = true;
+          tokens.push(Token::AnyChars);
+        },
+        '?' => {
+          has_wildcard = true;
+          tokens.push(Token::AnyChar);
+        },
+        '[' => {
+          has_wildcard = true;
+          idx += 1;
+          let negated = idx < chars.len() && (chars[idx] == '^' || chars[idx] == '!');
+          if negated {
+            idx += 1;
+          }
+
+          let mut ranges = Vec::new();
+          while idx < chars.len() && chars[idx] != ']' {
+            let lo = try!(unescape(&mut idx));
+            let mut hi = lo;
+            if idx + 2 < chars.len() && chars[idx + 1] == '-' && chars[idx + 2] != ']' {
+              idx += 2;
+              hi = try!(unescape(&mut idx));
+              if hi < lo {
+                return Err(invalid_pattern(glob, "illegal character range"));
+              }
+            }
+            ranges.push((lo, hi));
+            idx += 1;
+          }
+
+          if idx == chars.len() {
+            return Err(invalid_pattern(glob, "unclosed '['"));
+          }
+          if ranges.is_empty() {
+            return Err(invalid_pattern(glob, "empty character class"));
+          }
+          tokens.push(Token::Class { negated: negated, ranges: ranges });
+        },
+        _ => tokens.push(Token::Char(try!(unescape(&mut idx))))
+      }
+      idx += 1;
+    }
+
+    Ok(Pattern { tokens: tokens, has_wildcard: has_wildcard })
+  }
+
+  /// The unescaped component, which is meaningful only without wildcards
+  fn literal(&self) -> String {
+    self.tokens.iter().filter_map(|token| match *token {
+      Token::Char(c) => Some(c),
+      _ => None
+    }).collect()
+  }
+
+  fn matches(&self, name: &str) -> bool {
+    let chars: Vec<char> = name.chars().collect();
+    matches_from(&self.tokens, &chars)
+  }
+}
+
+/// Match greedily, in linear space and at worst O(tokens * name) time. On a
+/// mismatch, only the last ```*``` needs to take one more character, because
+/// whatever an earlier ```*``` would take instead, the last one can take as well.
+fn matches_from(tokens: &[Token], name: &[char]) -> bool {
+  let (mut t, mut n) = (0, 0);
+  // the token after the last '*', and the position of the name it resumes from
+  let mut backtrack: Option<(usize, usize)> = None;
+
+  while n < name.len() {
+    match tokens.get(t) {
+      Some(&Token::AnyChars) => {
+        t += 1;
+        backtrack = Some((t, n));
+        continue;
+      },
+      Some(token) if token.matches(name[n]) => {
+        t += 1;
+        n += 1;
+        continue;
+      },
+      _ => {}
+    }
+
+    match backtrack {
+      Some((star_t, star_n)) => {
+        t = star_t;
+        n = star_n + 1;
+        backtrack = Some((star_t, n));
+      },
+      None => return false
+    }
+  }
+
+  tokens[t..].iter().all(|token| match *token {
+    Token::AnyChars => true,
+    _ => false
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use native::MiniDfsConf;
+  use minidfs::*;
+  use dfs::HdfsFsCache;
+  use super::{expand_braces, Pattern};
+
+  fn matches(glob: &str, name: &str) -> bool {
+    Pattern::compile(glob).ok().unwrap().matches(name)
+  }
+
+  #[test]
+  fn test_pattern() {
+    assert!(matches("part-*.gz", "part-00000.gz"));
+    assert!(matches("part-*.gz", "part-.gz"));
+    assert!(!matches("part-*.gz", "part-00000.gz.tmp"));
+    assert!(matches("?b?", "abc"));
+    assert!(!matches("?b?", "ab"));
+    assert!(matches("[0-9]*", "2026"));
+    assert!(!matches("[0-9]*", "x2026"));
+    assert!(matches("[ab-d]", "c"));
+    assert!(!matches("[^a-c]", "b"));
+    assert!(matches("[!a-c]", "d"));
+    assert!(matches("\\*", "*"));
+    assert!(!matches("\\*", "a"));
+    assert!(matches("[\\]]", "]"));
+    assert!(matches("*a*b", "xaxab"));
+    assert!(matches("a*b*c", "abcbc"));
+    assert!(!matches("a*b*c", "abcb"));
+    assert!(matches("**", ""));
+    assert!(!matches("a*a*a*a*a*a*a*a*b", &"a".repeat(64)));
+
+    assert!(!Pattern::compile("plain").ok().unwrap().has_wildcard);
+    assert_eq!("a*b", Pattern::compile("a\\*b").ok().unwrap().literal());
+    assert!(Pattern::compile("[abc").is_err());
+    assert!(Pattern::compile("[]").is_err());
+    assert!(Pattern::compile("[z-a]").is_err());
+    assert!(Pattern::compile("abc\\").is_err());
+  }
+
+  #[test]
+  fn test_expand_braces() {
+    assert_eq!(vec!["/a/b"], expand_braces("/a/b").ok().unwrap());
+    assert_eq!(vec!["/data/a/x", "/data/b/x"],
+      expand_braces("/data/{a,b}/x").ok().unwrap());
+    assert_eq!(vec!["/a1", "/a2", "/b"], expand_braces("/{a{1,2},b}").ok().unwrap());
+    assert_eq!(vec!["/x/y", "/z"], expand_braces("/{x/y,z}").ok().unwrap());
+    assert_eq!(vec!["/a", "/"], expand_braces("/{a,}").ok().unwrap());
+    assert_eq!(vec!["/\\{a,b}"], expand_braces("/\\{a,b}").ok().unwrap());
+    assert!(expand_braces("/{a,b").is_err());
+  }
+
+  #[test]
+  fn test_glob() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    for day in &["01", "02", "x3"] {
+      for part in &["part-0.gz", "part-1.gz", "_SUCCESS"] {
+        let path = format!("/logs/2026/10/{}/{}", day, part);
+        assert!(fs.create(&path).ok().unwrap().close().is_ok());
+      }
+    }
+
+    let names = |pattern: &str| -> Vec<String> {
+      let prefix = format!("hdfs://localhost:{}/logs/2026/10/", port);
+      fs.glob(pattern).ok().unwrap().iter()
+        .map(|status| status.name()[prefix.len()..].to_owned())
+        .collect()
+    };
+
+    assert_eq!(vec!["01/part-0.gz", "01/part-1.gz", "02/part-0.gz", "02/part-1.gz",
+      "x3/part-0.gz", "x3/part-1.gz"], names("/logs/2026/10/*/part-*.gz"));
+    assert_eq!(vec!["01/part-1.gz", "02/part-1.gz"],
+      names("/logs/2026/10/[0-9]*/part-[!0].gz"));
+    assert_eq!(vec!["01", "x3/_SUCCESS"], names("/logs/2026/10/{01,x3/_SUCCESS}"));
+    assert_eq!(vec!["02"], names(&format!("hdfs://localhost:{}/logs/*/?0/0?2", port)));
+
+    // a literal path is returned only if it exists
+    assert_eq!(vec!["01"], names("/logs/2026/10/01"));
+    assert!(names("/logs/2026/10/04").is_empty());
+    assert!(names("/logs/2026/10/*/not_exist").is_empty());
+    assert!(fs.glob("/logs/[0-9").is_err());
+
+    dfs.stop();
+  }
+}
@@ -22,9 +22,11 @@
 //! The original ``libhdfs`` implementation allows only one ``HdfsFs`` instance for the 
 //! same namenode because ``libhdfs`` only keeps a single ``hdfsFs`` entry for each namenode.
 //! As a result, you need to keep a singleton ``HdfsFsCache`` in an entire program, and
-//! you must get ``HdfsFs`` through only ``HdfsFsCache``. For it, you need to share 
-//! ``HdfsFsCache`` instance across all threads in the program. 
-//! Contrast, ``HdfsFs`` instance itself is thread-safe. 
+//! you must get ``HdfsFs`` through only ``HdfsFsCache``. For it, you can use the
+//! process-wide ``HdfsFsCache::global()``, or share your own ``HdfsFsCache`` across
+//! all threads in the program (e.g., via ``Arc``).
+//! ``HdfsFsCache`` and ``HdfsFs`` are ``Send`` and ``Sync``, and clones of ``HdfsFs``
+//! share the same connection.
 //!
 //! ## Usage
 //! in Cargo.toml:
@@ -72,24 +74,28 @@
 //! ## Example
 //!
 //! ```ignore
-//! use std::rc::Rc;
-//! use std::cell::RefCell;
-//! use hdfs::HdfsFsCache;
+//! use std::thread;
+//! use hdfs::{HdfsFs, HdfsFsCache};
 //! 
-//! // You must get HdfsFs instance through HdfsFsCache. Also, HdfsFsCache 
-//! // must be shared across all threads in the entire program in order to
+//! // You must get HdfsFs instance through HdfsFsCache. The global HdfsFsCache
+//! // is shared across all threads in the entire program in order to
 //! // avoid the thread-safe problem of the original libhdfs.
-//! let cache = Rc::new(RefCell::new(HdfsFsCache::new()));  
-//! let fs: HdfsFs = cache.borrow_mut().get("hdfs://localhost:8020/").ok().unwrap();
+//! let fs: HdfsFs = HdfsFsCache::global().get("hdfs://localhost:8020/").ok().unwrap();
 //! match fs.mkdir("/data") {
 //!   Ok(_) => { println!("/data has been created") },
 //!   Err(_)  => { panic!("/data creation has failed") }
 //! }; 
+//!
+//! // HdfsFs can be moved to other threads.
+//! thread::spawn(move || {
+//!   assert!(fs.exist("/data"));
+//! }).join().unwrap();
 //! ```
 
 #[macro_use] extern crate itertools;
 extern crate libc;
 #[macro_use] extern crate log;
+#[cfg(feature = "serde")] #[macro_use] extern crate serde;
 extern crate url;
 
 mod err;
@@ -106,4 +112,27 @@ pub use dfs::*;
 pub mod minidfs;
 
 mod util;
-pub use util::HdfsUtil;
+pub use util::{CopyOptions, CopySummary, HdfsUtil, OverwritePolicy};
+
+/// Recursive directory walker
+mod walk;
+pub use walk::{Walk, WalkOrder};
+
+/// Glob pattern expansion of paths
+mod glob;
+
+/// Vectored positional reads
+mod ranges;
+pub use ranges::{RangeBuffers, ReadRangesOptions};
+
+/// Normalized HDFS paths
+mod path;
+pub use path::HdfsPath;
+
+/// Permission of files and directories
+mod perm;
+pub use perm::FsPermission;
+
+/// Hadoop configuration loaded by libhdfs
+mod conf;
+pub use conf::HdfsConf;
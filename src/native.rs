@@ -25,6 +25,9 @@ pub enum hdfsFile {}
 /// Opaque Pointer of hdfsBuilder
 pub enum hdfsBuilder {}
 
+/// Opaque Pointer of hdfsStreamBuilder
+pub enum hdfsStreamBuilder {}
+
 /// Opaque Pointer of hadoopRzOptions
 pub enum hadoopRzOptions {}
 
@@ -40,6 +43,9 @@ pub type tOffset = int64_t;
 /// port
 pub type tPort = uint16_t;
 
+/// errno which libhdfs sets for Java exceptions not mapped to any other errno
+pub const EINTERNAL: c_int = 255;
+
 #[repr(C)]
 pub enum tObjectKind 
 {
@@ -350,6 +356,79 @@ extern "C" {
                       bufferSize: c_int, replication: c_short, 
                       blocksize: int32_t) -> *const hdfsFile;
 
+  /// Create an HDFS stream builder, which opens a file like hdfsOpenFile but
+  /// takes a 64-bit block size. It is available since libhdfs of Hadoop 3.0,
+  /// so it requires the ```hadoop3``` feature.
+  ///
+  /// #### Params
+  /// * ```fs``` - The configured filesystem handle.
+  /// * ```path``` - The full path to the file.
+  /// * ```flags``` - The same flags as hdfsOpenFile.
+  ///
+  /// #### Return
+  /// Returns an HDFS stream builder, or ```NULL``` on error with errno set.
+  #[cfg(feature = "hadoop3")]
+  pub fn hdfsStreamBuilderAlloc(fs: *const hdfsFS, path: *const c_char, flags: c_int)
+    -> *mut hdfsStreamBuilder;
+
+  /// Free an HDFS stream builder which has not been built.
+  ///
+  /// #### Params
+  /// * ```bld``` - The HDFS stream builder.
+  #[cfg(feature = "hadoop3")]
+  pub fn hdfsStreamBuilderFree(bld: *mut hdfsStreamBuilder);
+
+  /// Set the buffer size of an HDFS stream builder.
+  ///
+  /// #### Return
+  /// Returns 0 on success, or -1 with errno set on error.
+  #[cfg(feature = "hadoop3")]
+  pub fn hdfsStreamBuilderSetBufferSize(bld: *mut hdfsStreamBuilder, bufferSize: int32_t)
+    -> c_int;
+
+  /// Set the replication of an HDFS stream builder. Only valid when writing.
+  ///
+  /// #### Return
+  /// Returns 0 on success, or -1 with errno set on error.
+  #[cfg(feature = "hadoop3")]
+  pub fn hdfsStreamBuilderSetReplication(bld: *mut hdfsStreamBuilder, replication: int16_t)
+    -> c_int;
+
+  /// Set the block size of an HDFS stream builder. Only valid when writing.
+  ///
+  /// #### Return
+  /// Returns 0 on success, or -1 with errno set on error.
+  #[cfg(feature = "hadoop3")]
+  pub fn hdfsStreamBuilderSetDefaultBlockSize(bld: *mut hdfsStreamBuilder,
+    defaultBlockSize: int64_t) -> c_int;
+
+  /// Open the file of an HDFS stream builder. The builder is freed whether
+  /// or not it succeeds.
+  ///
+  /// #### Return
+  /// Returns the file handle, or ```NULL``` with errno set on error.
+  #[cfg(feature = "hadoop3")]
+  pub fn hdfsStreamBuilderBuild(bld: *mut hdfsStreamBuilder) -> *const hdfsFile;
+
+  /// Truncate the file in the indicated path to the indicated size.
+  /// It is available since libhdfs of Hadoop 2.7, so it requires the
+  /// ```hadoop2_7``` feature.
+  ///
+  /// #### Params
+  /// * ```fs``` - The configured filesystem handle.
+  /// * ```path``` - The path to the file.
+  /// * ```newlength``` - The size the file is to be truncated to.
+  ///
+  /// #### Return
+  /// Returns 1 if the file has been truncated to the desired newlength and is
+  /// immediately available to be reused for write operations such as append.
+  /// Returns 0 if a background process of adjusting the length of the last
+  /// block has been started, and clients should wait for it to complete before
+  /// proceeding with further file updates. Returns -1 on error.
+  #[cfg(feature = "hadoop2_7")]
+  pub fn hdfsTruncateFile(fs: *const hdfsFS, path: *const c_char,
+    newlength: tOffset) -> c_int;
+
 
   /// Close an open file. 
   ///
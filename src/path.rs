@@ -0,0 +1,292 @@
+use std::fmt;
+
+/// A normalized HDFS path, which is either fully qualified (e.g.,
+/// ```hdfs://localhost:8020/dir/file```), absolute (e.g., ```/dir/file```)
+/// or relative (e.g., ```dir/file```).
+///
+/// On creation, ```.``` and ```..``` segments, duplicate slashes and trailing
+/// slashes are removed. An empty relative path is represented as ```.```.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HdfsPath {
+  uri: String,
+  /// the byte offset where the path component starts
+  path_start: usize
+}
+
+/// Split a string into ```scheme://authority``` and the path component.
+fn split_uri(s: &str) -> (&str, &str) {
+  if let Some(idx) = s.find("://") {
+    let scheme = &s[..idx];
+    let valid_scheme = scheme.chars().next().map_or(false, |c| c.is_alphabetic()) &&
+      scheme.chars().all(|c| c.is_alphanumeric() || c == '+' || c == '-' || c == '.');
+
+    if valid_scheme {
+      let path_start = s[idx + 3..].find('/').map_or(s.len(), |pos| idx + 3 + pos);
+      return (&s[..path_start], &s[path_start..]);
+    }
+  }
+
+  ("", s)
+}
+
+/// Remove ```.```, ```..```, duplicate and trailing slashes.
+fn normalize(path: &str, absolute: bool) -> String {
+  let mut segments: Vec<&str> = Vec::new();
+
+  for seg in path.split('/') {
+    match seg {
+      "" | "." => {},
+      ".." => {
+        if segments.last().map_or(false, |&last| last != "..") {
+          segments.pop();
+        } else if !absolute {
+          // unlike a relative path, '..' of the root is the root itself
+          segments.push("..");
+        }
+      },
+      _ => segments.push(seg)
+    }
+  }
+
+  if absolute {
+    format!("/{}", segments.join("/"))
+  } else if segments.is_empty() {
+    ".".to_owned()
+  } else {
+    segments.join("/")
+  }
+}
+
+impl HdfsPath {
+  /// Parse and normalize a path.
+  pub fn new(s: &str) -> HdfsPath {
+    let (prefix, path) = split_uri(s);
+    // a qualified path is always absolute
+    let absolute = !prefix.is_empty() || path.starts_with('/');
+
+    HdfsPath {
+      uri: format!("{}{}", prefix, normalize(path, absolute)),
+      path_start: prefix.len()
+    }
+  }
+
+  fn with_prefix(&self, path: &str) -> HdfsPath {
+    HdfsPath::new(&format!("{}{}", &self.uri[..self.path_start], path))
+  }
+
+  /// Get the whole path as a string.
+  #[inline]
+  pub fn as_str(&self) -> &str {
+    &self.uri
+  }
+
+  /// Get the scheme (e.g., ```hdfs```) if qualified.
+  pub fn scheme(&self) -> Option<&str> {
+    self.uri[..self.path_start].find("://").map(|idx| &self.uri[..idx])
+  }
+
+  /// Get the authority (e.g., ```localhost:8020```) if qualified.
+  pub fn authority(&self) -> Option<&str> {
+    self.uri[..self.path_start].find("://").map(|idx| &self.uri[idx + 3..self.path_start])
+  }
+
+  /// Get the path component without the scheme and the authority.
+  #[inline]
+  pub fn path(&self) -> &str {
+    &self.uri[self.path_start..]
+  }
+
+  /// Does it have a scheme and an authority?
+  #[inline]
+  pub fn is_qualified(&self) -> bool {
+    self.path_start > 0
+  }
+
+  /// Does the path component start with ```/```?
+  #[inline]
+  pub fn is_absolute(&self) -> bool {
+    self.path().starts_with('/')
+  }
+
+  /// Is it the root directory?
+  #[inline]
+  pub fn is_root(&self) -> bool {
+    self.path() == "/"
+  }
+
+  /// Append a path. If ```other``` is qualified, it is returned as it is. If
+  /// ```other``` is absolute, it replaces the path component of this path.
+  pub fn join(&self, other: &str) -> HdfsPath {
+    let other = HdfsPath::new(other);
+
+    if other.is_qualified() {
+      other
+    } else if other.is_absolute() {
+      self.with_prefix(other.path())
+    } else {
+      self.with_prefix(&format!("{}/{}", self.path(), other.path()))
+    }
+  }
+
+  /// Get the parent directory. It returns None for the root directory and
+  /// for relative paths without any parent (e.g., ```.``` and ```..```).
+  pub fn parent(&self) -> Option<HdfsPath> {
+    if self.file_name().is_none() {
+      return None;
+    }
+
+    let path = self.path();
+    Some(match path.rfind('/') {
+      Some(0) => self.with_prefix("/"),
+      Some(idx) => self.with_prefix(&path[..idx]),
+      None => HdfsPath::new(".")
+    })
+  }
+
+  /// Get the last segment of the path. It returns None for the root directory,
+  /// ```.``` and ```..```.
+  pub fn file_name(&self) -> Option<&str> {
+    let path = self.path();
+    let name = path.rfind('/').map_or(path, |idx| &path[idx + 1..]);
+
+    match name {
+      "" | "." | ".." => None,
+      name => Some(name)
+    }
+  }
+
+  /// Get the extension of the file name, i.e., the part after the last dot.
+  /// A leading dot of hidden files (e.g., ```.bashrc```) does not start an extension.
+  pub fn extension(&self) -> Option<&str> {
+    self.file_name().and_then(|name| {
+      match name.rfind('.') {
+        Some(0) | None => None,
+        Some(idx) => Some(&name[idx + 1..])
+      }
+    })
+  }
+
+  /// Make this path fully qualified against a base URL like ```HdfsFs::url()```
+  /// (e.g., ```hdfs://localhost:8020/```). Relative paths are resolved against
+  /// the path component of the base URL.
+  pub fn qualify(&self, base_url: &str) -> HdfsPath {
+    HdfsPath::new(base_url).join(self.as_str())
+  }
+
+  /// Get only the path component if this path is unqualified or belongs to
+  /// the filesystem of ```base_url```. Otherwise, it returns None.
+  pub fn unqualify(&self, base_url: &str) -> Option<HdfsPath> {
+    if !self.is_qualified() {
+      return Some(self.clone());
+    }
+
+    let base = HdfsPath::new(base_url);
+    let same_fs = base.is_qualified() &&
+      self.scheme().unwrap().eq_ignore_ascii_case(base.scheme().unwrap()) &&
+      self.authority().unwrap().eq_ignore_ascii_case(base.authority().unwrap());
+
+    if same_fs {
+      Some(self.without_scheme())
+    } else {
+      None
+    }
+  }
+
+  /// Get only the path component.
+  pub fn without_scheme(&self) -> HdfsPath {
+    HdfsPath { uri: self.path().to_owned(), path_start: 0 }
+  }
+}
+
+impl<'a> From<&'a str> for HdfsPath {
+  fn from(s: &'a str) -> HdfsPath {
+    HdfsPath::new(s)
+  }
+}
+
+impl From<String> for HdfsPath {
+  fn from(s: String) -> HdfsPath {
+    HdfsPath::new(&s)
+  }
+}
+
+impl AsRef<str> for HdfsPath {
+  fn as_ref(&self) -> &str {
+    &self.uri
+  }
+}
+
+impl fmt::Display for HdfsPath {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.uri)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::HdfsPath;
+
+  fn path(s: &str) -> HdfsPath {
+    HdfsPath::new(s)
+  }
+
+  #[test]
+  fn test_parse_and_normalize() {
+    let p = path("hdfs://localhost:8020//dir/./sub/../file.txt/");
+    assert_eq!("hdfs://localhost:8020/dir/file.txt", p.as_str());
+    assert_eq!(Some("hdfs"), p.scheme());
+    assert_eq!(Some("localhost:8020"), p.authority());
+    assert_eq!("/dir/file.txt", p.path());
+    assert!(p.is_qualified());
+    assert!(p.is_absolute());
+
+    assert_eq!("hdfs://localhost:8020/", path("hdfs://localhost:8020").as_str());
+    assert_eq!("/", path("/..").as_str());
+    assert_eq!("/a", path("//a//b/..").as_str());
+    assert_eq!("../a", path("x/../../a").as_str());
+    assert_eq!(".", path("").as_str());
+    assert_eq!(".", path("a/..").as_str());
+    assert_eq!("a:b/c", path("a:b//c").as_str());
+    assert!(!path("dir/file").is_absolute());
+    assert_eq!(None, path("/dir").scheme());
+  }
+
+  #[test]
+  fn test_components() {
+    let p = path("/dir/archive.tar.gz");
+    assert_eq!(Some("archive.tar.gz"), p.file_name());
+    assert_eq!(Some("gz"), p.extension());
+    assert_eq!(Some(path("/dir")), p.parent());
+    assert_eq!(Some(path("/")), path("/dir").parent());
+    assert_eq!(None, path("/").parent());
+    assert_eq!(None, path("/").file_name());
+    assert_eq!(Some(path(".")), path("dir").parent());
+    assert_eq!(None, path("..").parent());
+    assert_eq!(None, path("/.bashrc").extension());
+    assert_eq!(None, path("/dir/file").extension());
+    assert_eq!(Some(path("hdfs://nn:8020/")), path("hdfs://nn:8020/dir").parent());
+  }
+
+  #[test]
+  fn test_join() {
+    let base = path("hdfs://nn:8020/dir");
+    assert_eq!("hdfs://nn:8020/dir/a/b", base.join("a/b").as_str());
+    assert_eq!("hdfs://nn:8020/a", base.join("../a").as_str());
+    assert_eq!("hdfs://nn:8020/x", base.join("/x").as_str());
+    assert_eq!("hdfs://other:8020/x", base.join("hdfs://other:8020/x").as_str());
+    assert_eq!("dir/a", path("dir").join("a").as_str());
+  }
+
+  #[test]
+  fn test_qualify() {
+    let url = "hdfs://localhost:8020/";
+    assert_eq!(path("hdfs://localhost:8020/dir1/0"), path("/dir1/0").qualify(url));
+    assert_eq!(path("hdfs://localhost:8020/dir1/0"), path("dir1/0").qualify(url));
+    assert_eq!(path("hdfs://nn:1/x"), path("hdfs://nn:1/x").qualify(url));
+
+    assert_eq!(Some(path("/dir1/0")), path("hdfs://localhost:8020/dir1/0").unqualify(url));
+    assert_eq!(Some(path("/dir1/0")), path("HDFS://LOCALHOST:8020/dir1/0").unqualify(url));
+    assert_eq!(Some(path("/dir1/0")), path("/dir1/0").unqualify(url));
+    assert_eq!(None, path("hdfs://nn:1/dir1/0").unqualify(url));
+  }
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+use std::str::FromStr;
+
+use err::HdfsErr;
+
+const STICKY_BIT: u16 = 0o1000;
+
+/// Permission of a file or directory, i.e., the permission bits for
+/// the owner, the group and others, and the sticky bit.
+///
+/// It can be parsed from an octal mode (e.g., ```"755"```, ```"1777"```) or from
+/// the form of ```ls -l``` (e.g., ```"rwxr-x---"```), and is displayed in the latter.
+/// Symbolic modes like ```"u+rwx,g-w"``` are applied with ```apply_symbolic```.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FsPermission {
+  mode: u16
+}
+
+fn invalid_permission(s: &str) -> HdfsErr {
+  HdfsErr::InvalidArgument(format!("invalid permission '{}'", s))
+}
+
+impl FsPermission {
+  /// Create a permission from mode bits (e.g., ```0o755```).
+  /// Bits other than the permission bits and the sticky bit are ignored.
+  pub fn from_mode(mode: u16) -> FsPermission {
+    FsPermission { mode: mode & 0o1777 }
+  }
+
+  /// The default permission of new files before applying umask (```rw-rw-rw-```)
+  pub fn file_default() -> FsPermission {
+    FsPermission::from_mode(0o666)
+  }
+
+  /// The default permission of new directories before applying umask (```rwxrwxrwx```)
+  pub fn dir_default() -> FsPermission {
+    FsPermission::from_mode(0o777)
+  }
+
+  /// Parse an octal mode like ```"755"``` or ```"01777"```.
+  pub fn from_octal(s: &str) -> Result<FsPermission, HdfsErr> {
+    if s.is_empty() || s.len() > 5 {
+      return Err(invalid_permission(s));
+    }
+
+    match u16::from_str_radix(s, 8) {
+      Ok(mode) if mode <= 0o1777 => Ok(FsPermission::from_mode(mode)),
+      _ => Err(invalid_permission(s))
+    }
+  }
+
+  /// Get the mode bits.
+  #[inline]
+  pub fn mode(&self) -> u16 {
+    self.mode
+  }
+
+  /// Is the sticky bit set?
+  #[inline]
+  pub fn sticky_bit(&self) -> bool {
+    self.mode & STICKY_BIT != 0
+  }
+
+  /// Get a copy with the sticky bit set or cleared.
+  pub fn with_sticky_bit(&self, sticky: bool) -> FsPermission {
+    if sticky {
+      FsPermission::from_mode(self.mode | STICKY_BIT)
+    } else {
+      FsPermission::from_mode(self.mode & !STICKY_BIT)
+    }
+  }
+
+  /// Clear the permission bits set in ```umask```, like creating a file does.
+  pub fn apply_umask(&self, umask: FsPermission) -> FsPermission {
+    FsPermission::from_mode(self.mode & !(umask.mode & 0o777))
+  }
+
+  /// Apply comma-separated symbolic modes of chmod (e.g., ```"u+rwx,g-w,o="```).
+  ///
+  /// Each clause consists of users (```u```, ```g```, ```o``` or ```a```; all if
+  /// omitted), an operator (```+```, ```-``` or ```=```) and permissions (```r```,
+  /// ```w```, ```x```, ```t``` for the sticky bit, and ```X``` which means
+  /// ```x``` if ```is_directory``` or if any execute bit is already set).
+  pub fn apply_symbolic(&self, spec: &str, is_directory: bool)
+      -> Result<FsPermission, HdfsErr> {
+
+    let mut mode = self.mode;
+
+    for clause in spec.split(',') {
+      let chars: Vec<char> = clause.chars().collect();
+      let mut idx = 0;
+
+      let mut who: u16 = 0;
+      while idx < chars.len() {
+        who |= match chars[idx] {
+          'u' => 0o700,
+          'g' => 0o070,
+          'o' => 0o007,
+          'a' => 0o777,
+          _ => break
+        };
+        idx += 1;
+      }
+      // the sticky bit belongs to others
+      let sticky_allowed = who == 0 || who & 0o007 != 0;
+      if who == 0 {
+        who = 0o777;
+      }
+
+      if idx == chars.len() {
+        return Err(invalid_permission(spec));
+      }
+
+      while idx < chars.len() {
+        let op = chars[idx];
+        if op != '+' && op != '-' && op != '=' {
+          return Err(invalid_permission(spec));
+        }
+        idx += 1;
+
+        let mut bits: u16 = 0;
+        let mut sticky = false;
+        while idx < chars.len() {
+          bits |= match chars[idx] {
+            'r' => 0o444,
+            'w' => 0o222,
+            'x' => 0o111,
+            'X' if is_directory || mode & 0o111 != 0 => 0o111,
+            'X' => 0,
+            't' => {
+              sticky = true;
+              0
+            },
+            _ => break
+          };
+          idx += 1;
+        }
+
+        let bits = (bits & who) | if sticky && sticky_allowed { STICKY_BIT } else { 0 };
+        mode = match op {
+          '+' => mode | bits,
+          '-' => mode & !bits,
+          _ => {
+            let cleared = who | if sticky_allowed { STICKY_BIT } else { 0 };
+            (mode & !cleared) | bits
+          }
+        };
+      }
+    }
+
+    Ok(FsPermission::from_mode(mode))
+  }
+
+  /// Parse the form of ```ls -l``` like ```"rwxr-x--t"```, with an optional
+  /// leading file type character.
+  fn from_ls(s: &str) -> Result<FsPermission, HdfsErr> {
+    let chars: Vec<char> = s.chars().collect();
+    let chars = match chars.len() {
+      9 => &chars[..],
+      10 if chars[0] == '-' || chars[0] == 'd' => &chars[1..],
+      _ => return Err(invalid_permission(s))
+    };
+
+    let mut mode: u16 = 0;
+    for (idx, &c) in chars.iter().enumerate() {
+      let bit = 1 << (8 - idx);
+      let expected = ['r', 'w', 'x'][idx % 3];
+      mode |= match c {
+        '-' => 0,
+        't' if idx == 8 => bit | STICKY_BIT,
+        'T' if idx == 8 => STICKY_BIT,
+        c if c == expected => bit,
+        _ => return Err(invalid_permission(s))
+      };
+    }
+
+    Ok(FsPermission::from_mode(mode))
+  }
+}
+
+impl FromStr for FsPermission {
+  type Err = HdfsErr;
+
+  /// Parse an octal mode or the form of ```ls -l```.
+  fn from_str(s: &str) -> Result<FsPermission, HdfsErr> {
+    if s.chars().all(|c| c.is_digit(8)) {
+      FsPermission::from_octal(s)
+    } else {
+      FsPermission::from_ls(s)
+    }
+  }
+}
+
+impl fmt::Display for FsPermission {
+  /// Format like ```ls -l``` (e.g., ```rwxr-xr-x```, ```rwxrwxrwt```).
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut s = String::with_capacity(9);
+    for idx in 0..9 {
+      let c = ['r', 'w', 'x'][idx % 3];
+      s.push(if self.mode & (1 << (8 - idx)) != 0 { c } else { '-' });
+    }
+
+    if self.sticky_bit() {
+      s.pop();
+      s.push(if self.mode & 0o001 != 0 { 't' } else { 'T' });
+    }
+
+    f.write_str(&s)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::FsPermission;
+
+  fn perm(mode: u16) -> FsPermission {
+    FsPermission::from_mode(mode)
+  }
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(perm(0o755), "755".parse().ok().unwrap());
+    assert_eq!(perm(0o1777), "01777".parse().ok().unwrap());
+    assert_eq!(perm(0o750), "rwxr-x---".parse().ok().unwrap());
+    assert_eq!(perm(0o1777), "drwxrwxrwt".parse().ok().unwrap());
+    assert_eq!(perm(0o1776), "rwxrwxrwT".parse().ok().unwrap());
+    assert!("2777".parse::<FsPermission>().is_err());
+    assert!("rwxr-x--".parse::<FsPermission>().is_err());
+    assert!("rwxr-x--r".parse::<FsPermission>().is_err());
+    assert!("".parse::<FsPermission>().is_err());
+  }
+
+  #[test]
+  fn test_display() {
+    assert_eq!("rwxr-xr-x", perm(0o755).to_string());
+    assert_eq!("rw-------", perm(0o600).to_string());
+    assert_eq!("rwxrwxrwt", perm(0o1777).to_string());
+    assert_eq!("rwxrwxrwT", perm(0o1776).to_string());
+  }
+
+  #[test]
+  fn test_symbolic() {
+    let apply = |mode: u16, spec: &str| perm(mode).apply_symbolic(spec, false).ok().unwrap();
+
+    assert_eq!(perm(0o754), apply(0o644, "u+x,g-w+x,o=r"));
+    assert_eq!(perm(0o755), apply(0o644, "+x,g-w"));
+    assert_eq!(perm(0o700), apply(0o755, "go="));
+    assert_eq!(perm(0o1777), apply(0o777, "+t"));
+    assert_eq!(perm(0o777), apply(0o1777, "o-t"));
+    assert_eq!(perm(0o1777), apply(0o1777, "u-t"));
+    assert_eq!(perm(0o644), apply(0o644, "a+X"));
+    assert_eq!(perm(0o755), apply(0o744, "a+X"));
+    assert_eq!(perm(0o755), perm(0o644).apply_symbolic("a+X", true).ok().unwrap());
+    assert!(perm(0o644).apply_symbolic("u", false).is_err());
+    assert!(perm(0o644).apply_symbolic("u+q", false).is_err());
+  }
+
+  #[test]
+  fn test_umask() {
+    let umask = perm(0o022);
+    assert_eq!(perm(0o644), FsPermission::file_default().apply_umask(umask));
+    assert_eq!(perm(0o755), FsPermission::dir_default().apply_umask(umask));
+    assert!(perm(0o1777).apply_umask(umask).sticky_bit());
+    assert!(perm(0o777).with_sticky_bit(true).sticky_bit());
+  }
+}
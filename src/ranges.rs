@@ -0,0 +1,255 @@
+use std::cmp;
+use std::i32;
+use std::i64;
+use std::ops::Index;
+use std::panic;
+use std::thread;
+
+use libc::c_void;
+
+use dfs::HdfsFile;
+use err::HdfsErr;
+use native::*;
+
+/// Options of ```HdfsFile::read_ranges```
+#[derive(Clone, Debug)]
+pub struct ReadRangesOptions {
+  max_gap: u64,
+  max_merged_len: u64,
+  parallelism: usize
+}
+
+impl ReadRangesOptions {
+  /// By default, ranges less than 64 KiB apart are merged into reads of up to
+  /// 16 MiB, and the reads are performed one by one.
+  pub fn new() -> ReadRangesOptions {
+    ReadRangesOptions {
+      max_gap: 64 * 1024,
+      max_merged_len: 16 * 1024 * 1024,
+      parallelism: 1
+    }
+  }
+
+  /// Set the largest number of unrequested bytes between two ranges which are
+  /// still merged into a single read
+  pub fn set_max_gap(&mut self, max_gap: u64) -> &mut ReadRangesOptions {
+    self.max_gap = max_gap;
+    self
+  }
+
+  /// Set the largest length of a merged read. A single range longer than it
+  /// is still read at once.
+  pub fn set_max_merged_len(&mut self, max_len: u64) -> &mut ReadRangesOptions {
+    self.max_merged_len = max_len;
+    self
+  }
+
+  /// Set the number of threads performing merged reads at the same time
+  pub fn set_parallelism(&mut self, parallelism: usize) -> &mut ReadRangesOptions {
+    self.parallelism = cmp::max(parallelism, 1);
+    self
+  }
+}
+
+/// Buffers read by ```HdfsFile::read_ranges```, one for each requested range
+/// in the requested order.
+#[derive(Debug)]
+pub struct RangeBuffers {
+  chunks: Vec<Vec<u8>>,
+  /// (index of chunk, offset in chunk, length) of each range
+  slots: Vec<(usize, usize, usize)>
+}
+
+impl RangeBuffers {
+  /// The number of ranges
+  pub fn len(&self) -> usize {
+    self.slots.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.slots.is_empty()
+  }
+
+  /// Get the bytes of the i-th requested range.
+  pub fn get(&self, i: usize) -> Option<&[u8]> {
+    self.slots.get(i).map(|&(chunk, start, len)| {
+      if len == 0 {
+        &[][..]
+      } else {
+        &self.chunks[chunk][start..start + len]
+      }
+    })
+  }
+}
+
+impl Index<usize> for RangeBuffers {
+  type Output = [u8];
+
+  fn index(&self, i: usize) -> &[u8] {
+    self.get(i).expect("range index out of bounds")
+  }
+}
+
+/// A contiguous region of the file read at once
+#[derive(Debug, PartialEq)]
+struct Chunk {
+  offset: u64,
+  len: u64
+}
+
+/// Merge ranges into chunks, and locate each range in the chunks.
+fn coalesce(ranges: &[(u64, u64)], opts: &ReadRangesOptions)
+    -> Result<(Vec<Chunk>, Vec<(usize, usize, usize)>), HdfsErr> {
+
+  for &(offset, len) in ranges {
+    if offset.checked_add(len).map_or(true, |end| end > i64::MAX as u64) {
+      return Err(HdfsErr::InvalidArgument(
+        format!("invalid range (offset: {}, len: {})", offset, len)));
+    }
+  }
+
+  let mut order: Vec<usize> = (0..ranges.len()).collect();
+  order.sort_by_key(|&i| ranges[i].0);
+
+  let mut chunks: Vec<Chunk> = Vec::new();
+  let mut slots = vec![(0, 0, 0); ranges.len()];
+
+  for i in order {
+    let (offset, len) = ranges[i];
+    let end = offset + len;
+
+    // empty ranges need nothing to read
+    if len == 0 {
+      continue;
+    }
+
+    let merge = chunks.last().map_or(false, |last| {
+      let last_end = last.offset + last.len;
+      offset <= last_end.saturating_add(opts.max_gap) &&
+        cmp::max(end, last_end) - last.offset <= opts.max_merged_len
+    });
+
+    if merge {
+      let last = chunks.last_mut().unwrap();
+      last.len = cmp::max(end, last.offset + last.len) - last.offset;
+    } else {
+      chunks.push(Chunk { offset: offset, len: len });
+    }
+
+    let chunk_offset = chunks.last().unwrap().offset;
+    slots[i] = (chunks.len() - 1, (offset - chunk_offset) as usize, len as usize);
+  }
+
+  Ok((chunks, slots))
+}
+
+/// Raw handles of a file shared by reader threads. ```hdfsPread``` is thread-safe.
+#[derive(Clone, Copy)]
+struct SharedFile {
+  fs: *const hdfsFS,
+  file: *const hdfsFile
+}
+
+unsafe impl Send for SharedFile {}
+
+/// Read ```len``` bytes at ```offset```, failing if the file ends before.
+fn read_chunk(file: SharedFile, path: &str, offset: u64, len: usize)
+    -> Result<Vec<u8>, HdfsErr> {
+
+  let mut buf = vec![0u8; len];
+  let mut filled = 0;
+
+  while filled < len {
+    let pos = offset + filled as u64;
+    let read_len = unsafe {
+      hdfsPread(file.fs, file.file, pos as tOffset,
+        buf[filled..].as_mut_ptr() as *mut c_void,
+        cmp::min(len - filled, i32::MAX as usize) as tSize)
+    };
+
+    if read_len < 0 {
+      return Err(HdfsErr::last_os_error("read_ranges", path));
+    } else if read_len == 0 {
+      return Err(HdfsErr::UnexpectedEof { path: path.to_owned(), offset: pos });
+    }
+    filled += read_len as usize;
+  }
+
+  Ok(buf)
+}
+
+pub fn read_ranges(file: &HdfsFile, ranges: &[(u64, u64)], opts: &ReadRangesOptions)
+    -> Result<RangeBuffers, HdfsErr> {
+
+  let (chunks, slots) = try!(coalesce(ranges, opts));
+  let shared = SharedFile { fs: file.fs().raw(), file: file.raw() };
+  let workers = cmp::min(opts.parallelism, chunks.len());
+
+  let mut buffers: Vec<Vec<u8>> = Vec::with_capacity(chunks.len());
+
+  if workers <= 1 {
+    for chunk in &chunks {
+      buffers.push(try!(read_chunk(shared, file.path(), chunk.offset, chunk.len as usize)));
+    }
+  } else {
+    // the i-th worker reads the chunks whose indexes are i, i + workers, ...
+    let handles: Vec<_> = (0..workers).map(|worker| {
+      let path = file.path().to_owned();
+      let assigned: Vec<(u64, usize)> = chunks.iter().skip(worker).step_by(workers)
+        .map(|chunk| (chunk.offset, chunk.len as usize)).collect();
+
+      thread::spawn(move || {
+        assigned.into_iter()
+          .map(|(offset, len)| read_chunk(shared, &path, offset, len))
+          .collect::<Result<Vec<_>, HdfsErr>>()
+      })
+    }).collect();
+
+    // all threads must finish before the file can be used or closed again
+    let results: Vec<_> = handles.into_iter().map(|handle| handle.join()).collect();
+
+    let mut per_worker = Vec::with_capacity(workers);
+    for result in results {
+      match result {
+        Ok(Ok(bufs)) => per_worker.push(bufs.into_iter()),
+        Ok(Err(e)) => return Err(e),
+        Err(payload) => panic::resume_unwind(payload)
+      }
+    }
+
+    for i in 0..chunks.len() {
+      buffers.push(per_worker[i % workers].next().unwrap());
+    }
+  }
+
+  Ok(RangeBuffers { chunks: buffers, slots: slots })
+}
+
+#[cfg(test)]
+mod test {
+  use super::{coalesce, Chunk, ReadRangesOptions};
+
+  #[test]
+  fn test_coalesce() {
+    let mut opts = ReadRangesOptions::new();
+    opts.set_max_gap(10).set_max_merged_len(100);
+
+    let ranges = [(50, 10), (0, 10), (15, 5), (55, 20), (200, 150), (30, 0)];
+    let (chunks, slots) = coalesce(&ranges, &opts).ok().unwrap();
+
+    assert_eq!(vec![
+      Chunk { offset: 0, len: 20 },
+      Chunk { offset: 50, len: 25 },
+      Chunk { offset: 200, len: 150 }
+    ], chunks);
+    assert_eq!(vec![(1, 0, 10), (0, 0, 10), (0, 15, 5), (1, 5, 20), (2, 0, 150), (0, 0, 0)],
+      slots);
+
+    opts.set_max_merged_len(20);
+    let (chunks, _) = coalesce(&ranges, &opts).ok().unwrap();
+    assert_eq!(4, chunks.len());
+
+    assert!(coalesce(&[(u64::max_value(), 1)], &opts).is_err());
+    assert!(coalesce(&[], &opts).ok().unwrap().0.is_empty());
+  }
+}
@@ -1,24 +1,180 @@
 use std::ffi::{CString, CStr};
-use std::str;
-use libc::{c_char, c_int};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use libc::{c_char, c_int, time_t};
 
 use err::HdfsErr;
 use native::*;
-use dfs::HdfsFs;
+use dfs::{FileStatus, HdfsFs};
 
-pub fn str_to_chars(s: &str) -> *const c_char {
-  CString::new(s.as_bytes()).unwrap().as_ptr()
+/// Convert a path into a C string for libhdfs. The returned ```CString``` must
+/// be kept alive until libhdfs is done with its pointer.
+pub fn to_cstring(path: &str) -> Result<CString, HdfsErr> {
+  bytes_to_cstring(path.as_bytes())
 }
 
-pub fn chars_to_str<'a>(chars: *const c_char) -> &'a str {
-  let slice = unsafe { CStr::from_ptr(chars) }.to_bytes();
-  str::from_utf8(slice).unwrap()
+/// Convert a path given as bytes into a C string for libhdfs.
+pub fn bytes_to_cstring(path: &[u8]) -> Result<CString, HdfsErr> {
+  CString::new(path).map_err(|_| {
+    HdfsErr::InvalidPath(String::from_utf8_lossy(path).into_owned())
+  })
+}
+
+/// Copy a C string returned by libhdfs.
+pub fn chars_to_bytes(chars: *const c_char) -> Vec<u8> {
+  unsafe { CStr::from_ptr(chars) }.to_bytes().to_vec()
+}
+
+/// Copy a C string returned by libhdfs, replacing invalid UTF-8 sequences
+/// with ```U+FFFD```.
+pub fn chars_to_string(chars: *const c_char) -> String {
+  String::from_utf8_lossy(unsafe { CStr::from_ptr(chars) }.to_bytes()).into_owned()
 }
 
 pub fn bool_to_c_int(val: bool) -> c_int {
   if val { 1 } else { 0 }
 }
 
+pub fn time_t_to_system_time(secs: time_t) -> SystemTime {
+  if secs >= 0 {
+    UNIX_EPOCH + Duration::from_secs(secs as u64)
+  } else {
+    UNIX_EPOCH - Duration::from_secs(-(secs as i64) as u64)
+  }
+}
+
+/// libhdfs takes times in seconds since the epoch, where -1 means 'no change'.
+/// So, times before the epoch cannot be given.
+pub fn system_time_to_time_t(time: SystemTime) -> Result<time_t, HdfsErr> {
+  match time.duration_since(UNIX_EPOCH) {
+    Ok(duration) => Ok(duration.as_secs() as time_t),
+    Err(_) => Err(HdfsErr::InvalidArgument(
+      "times before the unix epoch are not supported".to_string()))
+  }
+}
+
+/// Get a unique hidden path in the same directory as ```path``` (e.g.,
+/// ```/dir/.file.1234-5678-0.tmp``` for ```/dir/file```).
+pub fn temp_path(path: &str) -> String {
+  static COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+
+  let (dir, name) = match path.rfind('/') {
+    Some(idx) => (&path[..idx + 1], &path[idx + 1..]),
+    None => ("", path)
+  };
+  let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+    .map(|elapsed| elapsed.subsec_nanos()).unwrap_or(0);
+
+  format!("{}.{}.{}-{}-{}.tmp", dir, name, process::id(), nanos,
+    COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// What to do when a destination file already exists during recursive copy or move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverwritePolicy {
+  /// Leave the destination file as it is, and count it as skipped.
+  Skip,
+  /// Replace the destination file. A directory is never replaced with a file,
+  /// and it is reported as ```FileAlreadyExists```.
+  Replace,
+  /// Report ```FileAlreadyExists``` for the file, and go on with other files.
+  Fail
+}
+
+/// Options of recursive copy and move
+#[derive(Clone, Debug)]
+pub struct CopyOptions {
+  overwrite: OverwritePolicy,
+  preserve_permission: bool,
+  preserve_owner: bool,
+  preserve_times: bool,
+  preserve_replication: bool
+}
+
+impl CopyOptions {
+  /// By default, existing destination files fail, and no attribute is preserved.
+  pub fn new() -> CopyOptions {
+    CopyOptions {
+      overwrite: OverwritePolicy::Fail,
+      preserve_permission: false,
+      preserve_owner: false,
+      preserve_times: false,
+      preserve_replication: false
+    }
+  }
+
+  /// Set what to do when a destination file already exists
+  pub fn set_overwrite(&mut self, policy: OverwritePolicy) -> &mut CopyOptions {
+    self.overwrite = policy;
+    self
+  }
+
+  /// Set TRUE in order to preserve permissions of files and directories
+  pub fn set_preserve_permission(&mut self, preserve: bool) -> &mut CopyOptions {
+    self.preserve_permission = preserve;
+    self
+  }
+
+  /// Set TRUE in order to preserve owners and groups of files and directories
+  pub fn set_preserve_owner(&mut self, preserve: bool) -> &mut CopyOptions {
+    self.preserve_owner = preserve;
+    self
+  }
+
+  /// Set TRUE in order to preserve modification and access times
+  pub fn set_preserve_times(&mut self, preserve: bool) -> &mut CopyOptions {
+    self.preserve_times = preserve;
+    self
+  }
+
+  /// Set TRUE in order to preserve replication factors of files
+  pub fn set_preserve_replication(&mut self, preserve: bool) -> &mut CopyOptions {
+    self.preserve_replication = preserve;
+    self
+  }
+
+  /// Set TRUE in order to preserve all of permissions, owners, times and replication
+  pub fn set_preserve_all(&mut self, preserve: bool) -> &mut CopyOptions {
+    self.set_preserve_permission(preserve)
+      .set_preserve_owner(preserve)
+      .set_preserve_times(preserve)
+      .set_preserve_replication(preserve)
+  }
+}
+
+/// Summary of recursive copy or move
+#[derive(Debug)]
+pub struct CopySummary {
+  /// the number of copied files
+  pub files: u64,
+  /// the number of created directories
+  pub directories: u64,
+  /// the number of copied bytes
+  pub bytes: u64,
+  /// the number of files skipped due to ```OverwritePolicy::Skip```
+  pub skipped: u64,
+  /// source paths which failed, with their errors
+  pub failures: Vec<(String, HdfsErr)>
+}
+
+impl CopySummary {
+  fn new() -> CopySummary {
+    CopySummary {
+      files: 0,
+      directories: 0,
+      bytes: 0,
+      skipped: 0,
+      failures: Vec::new()
+    }
+  }
+
+  /// Did all files and directories succeed?
+  pub fn is_success(&self) -> bool {
+    self.failures.is_empty()
+  }
+}
+
 /// Hdfs Utility
 pub struct HdfsUtil;
 
@@ -35,14 +191,16 @@ impl HdfsUtil {
   pub fn copy(src_fs: &HdfsFs, src: &str, dst_fs: &HdfsFs, dst: &str)
       -> Result<bool, HdfsErr> {
 
+    let c_src = try!(to_cstring(src));
+    let c_dst = try!(to_cstring(dst));
     let res = unsafe {
-      hdfsCopy(src_fs.raw(), str_to_chars(src), dst_fs.raw(), str_to_chars(dst))
+      hdfsCopy(src_fs.raw(), c_src.as_ptr(), dst_fs.raw(), c_dst.as_ptr())
     };
 
     if res == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(HdfsErr::last_os_error("copy", src))
     }
   }
 
@@ -56,14 +214,335 @@ impl HdfsUtil {
   pub fn mv(src_fs: &HdfsFs, src: &str, dst_fs: &HdfsFs, dst: &str)
       -> Result<bool, HdfsErr> {
 
+    let c_src = try!(to_cstring(src));
+    let c_dst = try!(to_cstring(dst));
     let res = unsafe {
-      hdfsMove(src_fs.raw(), str_to_chars(src), dst_fs.raw(), str_to_chars(dst))
+      hdfsMove(src_fs.raw(), c_src.as_ptr(), dst_fs.raw(), c_dst.as_ptr())
     };
 
     if res == 0 {
       Ok(true)
     } else {
-      Err(HdfsErr::Unknown)
+      Err(HdfsErr::last_os_error("mv", src))
     }
   }
-}
\ No newline at end of file
+
+  /// Copy a file or a directory recursively from one filesystem to another.
+  ///
+  /// It goes on after a file or a directory fails, and reports the failures
+  /// in the returned summary. Entries whose names are not valid UTF-8 are
+  /// reported as ```InvalidPath``` without being copied. An error is returned
+  /// only if the source cannot be found at all, or the destination is the
+  /// source or under it.
+  pub fn copy_recursive(src_fs: &HdfsFs, src: &str, dst_fs: &HdfsFs, dst: &str,
+      opts: &CopyOptions) -> Result<CopySummary, HdfsErr> {
+
+    try!(HdfsUtil::check_not_into_itself(src_fs, src, dst_fs, dst));
+
+    let mut summary = CopySummary::new();
+    try!(HdfsUtil::copy_tree(src_fs, src, dst_fs, dst, opts, &mut summary, None));
+    Ok(summary)
+  }
+
+  /// Move a file or a directory recursively from one filesystem to another.
+  ///
+  /// Within the same filesystem, it is just a rename if the destination does not
+  /// exist. Otherwise, each source file is deleted after it is copied, and
+  /// source directories are deleted if they become empty. Files which are skipped
+  /// or fail remain in the source.
+  pub fn mv_recursive(src_fs: &HdfsFs, src: &str, dst_fs: &HdfsFs, dst: &str,
+      opts: &CopyOptions) -> Result<CopySummary, HdfsErr> {
+
+    try!(HdfsUtil::check_not_into_itself(src_fs, src, dst_fs, dst));
+
+    if src_fs.url() == dst_fs.url() && !dst_fs.exist(dst) {
+      // count what is moved like the copy below does
+      let content = try!(src_fs.content_summary(src));
+      let mut summary = CopySummary::new();
+      if let Err(e) = src_fs.rename(src, dst) {
+        summary.failures.push((src.to_owned(), e));
+      } else {
+        summary.files = content.file_count;
+        summary.directories = content.directory_count;
+        summary.bytes = content.length;
+      }
+      return Ok(summary);
+    }
+
+    let mut summary = CopySummary::new();
+    let mut copied = Vec::new();
+    try!(HdfsUtil::copy_tree(src_fs, src, dst_fs, dst, opts, &mut summary,
+      Some(&mut copied)));
+
+    // copied entries are in pre-order, so deleting them in reverse order
+    // deletes files and sub directories before their parents.
+    for &(ref path, is_dir) in copied.iter().rev() {
+      if is_dir {
+        // directories keeping skipped or failed files remain
+        match src_fs.list_status(path) {
+          Ok(ref entries) if entries.is_empty() => {},
+          _ => continue
+        }
+      }
+
+      if let Err(e) = src_fs.delete(path, false) {
+        summary.failures.push((path.clone(), e));
+      }
+    }
+
+    Ok(summary)
+  }
+
+  /// Fail if ```dst``` is ```src``` or under it. Otherwise, copying a tree would
+  /// keep finding the directories it has just created.
+  fn check_not_into_itself(src_fs: &HdfsFs, src: &str, dst_fs: &HdfsFs, dst: &str)
+      -> Result<(), HdfsErr> {
+
+    let src_path = try!(src_fs.qualify(src));
+    let dst_path = try!(dst_fs.qualify(dst));
+
+    let mut ancestor = Some(dst_path);
+    while let Some(path) = ancestor {
+      if path == src_path {
+        return Err(HdfsErr::InvalidArgument(
+          format!("{} is the same as or a subdirectory of {}", dst, src)));
+      }
+      ancestor = path.parent();
+    }
+
+    Ok(())
+  }
+
+  /// Copy a tree, collecting the copied source paths into ```copied```
+  /// with whether they are directories.
+  fn copy_tree(src_fs: &HdfsFs, src: &str, dst_fs: &HdfsFs, dst: &str,
+      opts: &CopyOptions, summary: &mut CopySummary,
+      mut copied: Option<&mut Vec<(String, bool)>>) -> Result<(), HdfsErr> {
+
+    let root = try!(src_fs.get_file_status(src));
+    if root.is_file() {
+      if HdfsUtil::copy_entry(src_fs, &root, dst_fs, dst, opts, summary) {
+        if let Some(copied) = copied {
+          copied.push((root.name().to_owned(), false));
+        }
+      }
+      return Ok(());
+    }
+
+    // Times of directories change while their entries are being copied,
+    // so they are set after all entries are copied.
+    let mut dirs = Vec::new();
+    if HdfsUtil::copy_entry(src_fs, &root, dst_fs, dst, opts, summary) {
+      dirs.push((root.clone(), dst.to_owned()));
+    } else {
+      return Ok(());
+    }
+
+    let dst_root = dst.trim_end_matches('/');
+    // entries whose names are not valid UTF-8 cannot be given to the copy APIs
+    for entry in src_fs.walk(src).follow(|st| st.try_name().is_ok()) {
+      let status = match entry {
+        Ok(status) => status,
+        Err(e) => {
+          // the directory which could not be listed
+          let path = match e {
+            HdfsErr::Native { ref path, .. } if !path.is_empty() => path.clone(),
+            _ => src.to_owned()
+          };
+          summary.failures.push((path, e));
+          continue;
+        }
+      };
+
+      if status.try_name().is_err() {
+        summary.failures.push((status.name().to_owned(),
+          HdfsErr::InvalidPath(status.name().to_owned())));
+        continue;
+      }
+
+      // walked names are fully qualified like the name of the root
+      let dst_path = format!("{}{}", dst_root, &status.name()[root.name().len()..]);
+      if HdfsUtil::copy_entry(src_fs, &status, dst_fs, &dst_path, opts, summary) {
+        if status.is_directory() {
+          dirs.push((status.clone(), dst_path));
+        }
+        if let Some(ref mut copied) = copied {
+          copied.push((status.name().to_owned(), status.is_directory()));
+        }
+      }
+    }
+
+    if let Some(ref mut copied) = copied {
+      copied.insert(0, (root.name().to_owned(), true));
+    }
+
+    if opts.preserve_times {
+      for &(ref status, ref dst_path) in dirs.iter().rev() {
+        if let Err(e) = dst_fs.set_times(dst_path,
+            Some(status.modified()), Some(status.accessed())) {
+          summary.failures.push((status.name().to_owned(), e));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Copy a single file or create a single directory, and preserve its attributes.
+  /// It returns true if the entry is copied.
+  fn copy_entry(src_fs: &HdfsFs, status: &FileStatus, dst_fs: &HdfsFs, dst: &str,
+      opts: &CopyOptions, summary: &mut CopySummary) -> bool {
+
+    match HdfsUtil::try_copy_entry(src_fs, status, dst_fs, dst, opts) {
+      Ok(true) => {
+        if status.is_directory() {
+          summary.directories += 1;
+        } else {
+          summary.files += 1;
+          summary.bytes += status.len() as u64;
+        }
+        true
+      },
+      Ok(false) => {
+        summary.skipped += 1;
+        false
+      },
+      Err(e) => {
+        summary.failures.push((status.name().to_owned(), e));
+        false
+      }
+    }
+  }
+
+  fn try_copy_entry(src_fs: &HdfsFs, status: &FileStatus, dst_fs: &HdfsFs, dst: &str,
+      opts: &CopyOptions) -> Result<bool, HdfsErr> {
+
+    if status.is_directory() {
+      try!(dst_fs.mkdir(dst));
+    } else {
+      match dst_fs.get_file_status(dst) {
+        Ok(existing) => match opts.overwrite {
+          OverwritePolicy::Skip => return Ok(false),
+          OverwritePolicy::Fail => return Err(HdfsErr::FileAlreadyExists(dst.to_owned())),
+          OverwritePolicy::Replace => {
+            if existing.is_directory() {
+              return Err(HdfsErr::FileAlreadyExists(dst.to_owned()));
+            }
+            try!(dst_fs.delete(dst, false));
+          }
+        },
+        Err(ref e) if e.is_not_found() => {},
+        Err(e) => return Err(e)
+      }
+
+      try!(HdfsUtil::copy(src_fs, status.name(), dst_fs, dst));
+
+      if opts.preserve_replication {
+        try!(dst_fs.set_replication(dst, status.replica_count()));
+      }
+      if opts.preserve_times {
+        try!(dst_fs.set_times(dst, Some(status.modified()), Some(status.accessed())));
+      }
+    }
+
+    if opts.preserve_owner {
+      try!(dst_fs.chown(dst, status.owner(), status.group()));
+    }
+    if opts.preserve_permission {
+      try!(dst_fs.chmod(dst, status.permission()));
+    }
+
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::Write;
+
+  use native::MiniDfsConf;
+  use minidfs::*;
+  use dfs::HdfsFsCache;
+  use super::{CopyOptions, HdfsUtil, OverwritePolicy};
+  use perm::FsPermission;
+
+  #[test]
+  fn test_copy_and_move_recursive() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    // /src/f1 (3 bytes), /src/sub/f2 (5 bytes)
+    fs.mkdir("/src/sub").ok().unwrap();
+    let mut f1 = fs.create("/src/f1").ok().unwrap();
+    f1.write_all(b"abc").unwrap();
+    assert!(f1.close().is_ok());
+    let mut f2 = fs.create("/src/sub/f2").ok().unwrap();
+    f2.write_all(b"defgh").unwrap();
+    assert!(f2.close().is_ok());
+    fs.chmod("/src/sub/f2", FsPermission::from_mode(0o600)).ok().unwrap();
+
+    let mut opts = CopyOptions::new();
+    opts.set_preserve_all(true);
+    let summary = HdfsUtil::copy_recursive(&fs, "/src", &fs, "/dst", &opts).ok().unwrap();
+    assert!(summary.is_success());
+    assert_eq!(2, summary.files);
+    assert_eq!(2, summary.directories);
+    assert_eq!(8, summary.bytes);
+
+    let src_status = fs.get_file_status("/src/sub/f2").ok().unwrap();
+    let dst_status = fs.get_file_status("/dst/sub/f2").ok().unwrap();
+    assert_eq!(5, dst_status.len());
+    assert_eq!(0o600, dst_status.permission().mode());
+    assert_eq!(src_status.modified(), dst_status.modified());
+
+    // existing files fail by default, and are skipped or replaced on demand
+    let summary = HdfsUtil::copy_recursive(&fs, "/src", &fs, "/dst", &opts).ok().unwrap();
+    assert_eq!(2, summary.failures.len());
+    assert!(summary.failures.iter().all(|&(_, ref e)| e.is_already_exists()));
+
+    opts.set_overwrite(OverwritePolicy::Skip);
+    let summary = HdfsUtil::copy_recursive(&fs, "/src", &fs, "/dst", &opts).ok().unwrap();
+    assert!(summary.is_success());
+    assert_eq!(2, summary.skipped);
+    assert_eq!(0, summary.files);
+
+    opts.set_overwrite(OverwritePolicy::Replace);
+    let summary = HdfsUtil::copy_recursive(&fs, "/src", &fs, "/dst", &opts).ok().unwrap();
+    assert!(summary.is_success());
+    assert_eq!(2, summary.files);
+
+    // a directory is not replaced with a file
+    fs.mkdir("/dst/dir_or_file").ok().unwrap();
+    let summary = HdfsUtil::copy_recursive(&fs, "/src/f1", &fs, "/dst/dir_or_file", &opts)
+      .ok().unwrap();
+    assert!(summary.failures[0].1.is_already_exists());
+    assert!(fs.get_file_status("/dst/dir_or_file").ok().unwrap().is_directory());
+    fs.delete("/dst/dir_or_file", false).ok().unwrap();
+
+    // copying or moving into itself never ends, so it is rejected
+    assert!(HdfsUtil::copy_recursive(&fs, "/src", &fs, "/src/sub/copy", &opts).is_err());
+    assert!(HdfsUtil::mv_recursive(&fs, "/src", &fs, "/src", &opts).is_err());
+    assert!(!fs.exist("/src/sub/copy"));
+
+    // moving onto an existing tree copies and deletes sources
+    let summary = HdfsUtil::mv_recursive(&fs, "/src", &fs, "/dst", &opts).ok().unwrap();
+    assert!(summary.is_success());
+    assert!(!fs.exist("/src"));
+    assert!(fs.exist("/dst/sub/f2"));
+
+    // moving to a new path in the same filesystem is a rename, counted like a copy
+    let summary = HdfsUtil::mv_recursive(&fs, "/dst", &fs, "/moved", &opts).ok().unwrap();
+    assert!(summary.is_success());
+    assert_eq!(2, summary.files);
+    assert_eq!(2, summary.directories);
+    assert_eq!(8, summary.bytes);
+    assert!(!fs.exist("/dst"));
+    assert_eq!(3, fs.get_file_status("/moved/f1").ok().unwrap().len());
+
+    dfs.stop();
+  }
+}
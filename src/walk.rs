@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::vec;
+
+use dfs::{FileStatus, HdfsFs};
+use err::HdfsErr;
+
+/// Order in which ```Walk``` visits entries
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WalkOrder {
+  /// Visit the entries of a directory right after the directory (pre-order)
+  DepthFirst,
+  /// Visit all entries of a depth before entries of the next depth
+  BreadthFirst
+}
+
+/// Predicate on an entry, given to ```Walk::filter_entry``` and ```Walk::follow```
+type EntryPredicate<'a> = Box<dyn FnMut(&FileStatus) -> bool + 'a>;
+
+/// Recursive iterator over the entries under a directory, created by ```HdfsFs::walk```.
+///
+/// The given directory itself is not yielded, and its direct children are
+/// at depth 1. Each directory is listed only when the iterator reaches it.
+/// Directories whose names are not valid UTF-8 are listed by their exact bytes.
+///
+/// ```ignore
+/// for entry in fs.walk("/data").max_depth(2).filter_entry(|st| !st.name().ends_with(".tmp")) {
+///   let status = entry.ok().unwrap();
+///   println!("{}", status.name());
+/// }
+/// ```
+pub struct Walk<'a> {
+  fs: &'a HdfsFs,
+  order: WalkOrder,
+  max_depth: Option<usize>,
+  ignore_errors: bool,
+  filter: Option<EntryPredicate<'a>>,
+  follow: Option<EntryPredicate<'a>>,
+  /// directories to be listed, with the depth of their entries
+  to_list: VecDeque<(Vec<u8>, usize)>,
+  /// listings being visited. Depth-first order keeps a stack of them.
+  listings: Vec<(vec::IntoIter<FileStatus>, usize)>
+}
+
+impl<'a> Walk<'a> {
+  pub fn new(fs: &'a HdfsFs, path: &str) -> Walk<'a> {
+    let mut to_list = VecDeque::new();
+    to_list.push_back((path.as_bytes().to_vec(), 1));
+
+    Walk {
+      fs: fs,
+      order: WalkOrder::DepthFirst,
+      max_depth: None,
+      ignore_errors: false,
+      filter: None,
+      follow: None,
+      to_list: to_list,
+      listings: Vec::new()
+    }
+  }
+
+  /// Set the order of visiting entries. The default is ```WalkOrder::DepthFirst```.
+  pub fn order(mut self, order: WalkOrder) -> Walk<'a> {
+    self.order = order;
+    self
+  }
+
+  /// Yield only entries whose depth is ```max_depth``` or less.
+  pub fn max_depth(mut self, max_depth: usize) -> Walk<'a> {
+    self.max_depth = Some(max_depth);
+    self
+  }
+
+  /// Skip errors (e.g., directories removed or not permitted during the walk)
+  /// instead of yielding them. Either way, the walk goes on after an error.
+  pub fn ignore_errors(mut self, ignore: bool) -> Walk<'a> {
+    self.ignore_errors = ignore;
+    self
+  }
+
+  /// Yield only entries for which the predicate returns true. A skipped
+  /// directory is not descended into. Unlike ```Iterator::filter```, it prunes
+  /// whole subtrees, and errors are not filtered.
+  pub fn filter_entry<F>(mut self, predicate: F) -> Walk<'a>
+      where F: FnMut(&FileStatus) -> bool + 'a {
+    self.filter = Some(Box::new(predicate));
+    self
+  }
+
+  /// Descend only into directories for which the predicate returns true.
+  /// The directories themselves are still yielded.
+  pub fn follow<F>(mut self, predicate: F) -> Walk<'a>
+      where F: FnMut(&FileStatus) -> bool + 'a {
+    self.follow = Some(Box::new(predicate));
+    self
+  }
+}
+
+impl<'a> Iterator for Walk<'a> {
+  type Item = Result<FileStatus, HdfsErr>;
+
+  fn next(&mut self) -> Option<Result<FileStatus, HdfsErr>> {
+    loop {
+      // Depth-first order lists a directory right after yielding it, while
+      // breadth-first order lists it after finishing the current listing.
+      if self.order == WalkOrder::DepthFirst || self.listings.is_empty() {
+        if let Some((path, depth)) = self.to_list.pop_front() {
+          if self.max_depth.map_or(false, |max| depth > max) {
+            continue;
+          }
+
+          match self.fs.list_status_bytes(&path) {
+            Ok(list) => self.listings.push((list.into_iter(), depth)),
+            Err(e) => {
+              if self.ignore_errors {
+                warn!("Skipping {} during walk: {}", String::from_utf8_lossy(&path), e);
+              } else {
+                return Some(Err(e));
+              }
+            }
+          }
+          continue;
+        }
+      }
+
+      let next = match self.listings.last_mut() {
+        Some(&mut (ref mut iter, depth)) => iter.next().map(|status| (status, depth)),
+        None => return None
+      };
+
+      let (status, depth) = match next {
+        Some(next) => next,
+        None => {
+          self.listings.pop();
+          continue;
+        }
+      };
+
+      if let Some(ref mut filter) = self.filter {
+        if !filter(&status) {
+          continue;
+        }
+      }
+
+      if status.is_directory() {
+        let follow = match self.follow {
+          Some(ref mut follow) => follow(&status),
+          None => true
+        };
+
+        if follow {
+          let dir = (status.name_bytes().to_vec(), depth + 1);
+          match self.order {
+            WalkOrder::DepthFirst => self.to_list.push_front(dir),
+            WalkOrder::BreadthFirst => self.to_list.push_back(dir)
+          }
+        }
+      }
+
+      return Some(Ok(status));
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use native::MiniDfsConf;
+  use minidfs::*;
+  use dfs::HdfsFsCache;
+  use super::WalkOrder;
+
+  #[test]
+  fn test_walk() {
+    let mut conf = MiniDfsConf::new();
+    let dfs = MiniDFS::start(&mut conf).unwrap();
+    let port = dfs.namenode_port().unwrap();
+
+    let cache = HdfsFsCache::new();
+    let fs = cache.get(&format!("hdfs://localhost:{}/", port)).ok().unwrap();
+
+    // /walk/a/b/c, /walk/a/f1, /walk/d/f2
+    fs.mkdir("/walk/a/b/c").ok().unwrap();
+    fs.mkdir("/walk/d").ok().unwrap();
+    assert!(fs.create("/walk/a/f1").ok().unwrap().close().is_ok());
+    assert!(fs.create("/walk/d/f2").ok().unwrap().close().is_ok());
+
+    let prefix = format!("hdfs://localhost:{}/walk/", port);
+    let names = |order: WalkOrder, max_depth: usize| -> Vec<String> {
+      fs.walk("/walk")
+        .order(order)
+        .max_depth(max_depth)
+        .map(|entry| entry.ok().unwrap().name()[prefix.len()..].to_owned())
+        .collect()
+    };
+
+    let mut dfs_names = names(WalkOrder::DepthFirst, 10);
+    assert_eq!(6, dfs_names.len());
+    // a directory is followed by its entries in depth-first order
+    let a_idx = dfs_names.iter().position(|n| n == "a").unwrap();
+    assert!(dfs_names[a_idx + 1].starts_with("a/"));
+    assert!(dfs_names[a_idx + 2].starts_with("a/"));
+    dfs_names.sort();
+    assert_eq!(vec!["a", "a/b", "a/b/c", "a/f1", "d", "d/f2"], dfs_names);
+
+    let bfs_names = names(WalkOrder::BreadthFirst, 10);
+    let depths: Vec<usize> = bfs_names.iter().map(|n| n.split('/').count()).collect();
+    assert_eq!(vec![1, 1, 2, 2, 2, 3], depths);
+
+    assert_eq!(4, names(WalkOrder::DepthFirst, 2).len());
+    assert_eq!(0, names(WalkOrder::DepthFirst, 0).len());
+
+    // skip 'd/f2', and do not descend into 'a'
+    let mut filtered: Vec<String> = fs.walk("/walk")
+      .filter_entry(|st| !st.name().ends_with("/f2"))
+      .follow(|st| !st.name().ends_with("/a"))
+      .map(|entry| entry.ok().unwrap().name()[prefix.len()..].to_owned())
+      .collect();
+    filtered.sort();
+    assert_eq!(vec!["a", "d"], filtered);
+
+    // errors are yielded or skipped
+    assert!(fs.walk("/not_exist").next().unwrap().is_err());
+    assert!(fs.walk("/not_exist").ignore_errors(true).next().is_none());
+
+    dfs.stop();
+  }
+}